@@ -0,0 +1,316 @@
+pub mod envelope;
+pub mod length_counter;
+pub mod pulse;
+pub mod triangle;
+pub mod noise;
+pub mod dmc;
+pub mod filter;
+pub mod resampler;
+
+use crate::apu::dmc::DMC;
+use crate::apu::noise::Noise;
+use crate::apu::pulse::Pulse;
+use crate::apu::resampler::Resampler;
+use crate::nes::Region;
+use crate::apu::triangle::Triangle;
+
+pub const NTSC_CPU_CLOCK:f64 = 1_789_773.0;
+pub const DEFAULT_SAMPLE_RATE:f64 = 44_100.0;
+/* Audio frames are closed automatically after this many cycles when nobody ends them */
+const MAX_AUDIO_FRAME_CYCLES:u32 = 0x10000;
+/* Frame counter steps, in CPU cycles since the sequence started */
+const NTSC_FRAME_STEPS:[u32; 5] = [7457, 14913, 22371, 29829, 37281];
+const PAL_FRAME_STEPS:[u32; 5] = [8313, 16627, 24939, 33253, 41565];
+
+/**
+ * $4015 status bits
+ */
+#[allow(clippy::upper_case_acronyms)]
+pub enum StatusFlag {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    /* Sample bytes remaining */
+    DMC,
+    FrameInterrupt,
+    DMCInterrupt
+}
+
+impl StatusFlag {
+    pub fn mask(&self) -> u8 {
+        match self {
+            StatusFlag::Pulse1 => 0b0000_0001,
+            StatusFlag::Pulse2 => 0b0000_0010,
+            StatusFlag::Triangle => 0b0000_0100,
+            StatusFlag::Noise => 0b0000_1000,
+            StatusFlag::DMC => 0b0001_0000,
+            StatusFlag::FrameInterrupt => 0b0100_0000,
+            StatusFlag::DMCInterrupt => 0b1000_0000
+        }
+    }
+}
+
+/**
+ * 2A03 audio processing unit.
+ *
+ * $4000-$4003  Pulse 1
+ * $4004-$4007  Pulse 2
+ * $4008-$400B  Triangle
+ * $400C-$400F  Noise
+ * $4010-$4013  DMC
+ * $4015        Channel enable (write), length counter and IRQ status (read)
+ * $4017        Frame counter mode and IRQ inhibit (write)
+ *
+ * Clocked once per CPU cycle. The frame counter clocks envelopes and the
+ * triangle linear counter on quarter frames, length counters and sweeps on
+ * half frames, and raises the frame IRQ at the end of the 4-step sequence.
+ * DMC sample fetches are requested from the bus owner, which halts the CPU
+ * to perform them.
+ *
+ * The mixed output is fed to a band-limited resampler, end_audio_frame
+ * converts the cycles since the last call to samples at the host rate.
+ */
+#[allow(clippy::upper_case_acronyms)]
+pub struct APU {
+    pub pulse1:Pulse,
+    pub pulse2:Pulse,
+    pub triangle:Triangle,
+    pub noise:Noise,
+    pub dmc:DMC,
+    pub resampler:Resampler,
+    /* CPU cycles since the audio frame started */
+    audio_cycles:u32,
+    last_output:f32,
+    /* CPU cycles since power on */
+    pub cycles:u64,
+    frame_steps:[u32; 5],
+    five_step_mode:bool,
+    irq_inhibit:bool,
+    frame_irq:bool,
+    frame_cycle:u32,
+    /* CPU cycles until a $4017 write resets the frame sequence */
+    frame_reset_delay:Option<u8>
+}
+
+impl APU {
+    pub fn new() -> Self {
+        APU {
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::new(),
+            noise: Noise::new(),
+            dmc: DMC::new(),
+            resampler: Resampler::new(NTSC_CPU_CLOCK, DEFAULT_SAMPLE_RATE),
+            audio_cycles: 0,
+            last_output: 0.0,
+            cycles: 0,
+            frame_steps: NTSC_FRAME_STEPS,
+            five_step_mode: false,
+            irq_inhibit: false,
+            frame_irq: false,
+            frame_cycle: 0,
+            frame_reset_delay: None
+        }
+    }
+
+    /**
+     * Switches the frame counter, noise and DMC rates and the resampler
+     * input rate to the console region.
+     */
+    pub fn set_region(&mut self, region: Region) {
+        self.frame_steps = match region {
+            Region::NTSC => NTSC_FRAME_STEPS,
+            Region::PAL => PAL_FRAME_STEPS
+        };
+        self.noise.set_region(region);
+        self.dmc.set_region(region);
+        self.resampler.set_clock_rate(region.cpu_clock_rate());
+    }
+
+    /**
+     * Handles a CPU write to $4000-$4017.
+     */
+    pub fn write_register(&mut self, addr:u16, data:u8) {
+        let index = addr & 0x03;
+        match addr {
+            0x4000..=0x4003 => self.pulse1.write_register(index, data),
+            0x4004..=0x4007 => self.pulse2.write_register(index, data),
+            0x4008..=0x400B => self.triangle.write_register(index, data),
+            0x400C..=0x400F => self.noise.write_register(index, data),
+            0x4010..=0x4013 => self.dmc.write_register(index, data),
+            0x4015 => {
+                self.pulse1.length.set_enabled(data & StatusFlag::Pulse1.mask() != 0);
+                self.pulse2.length.set_enabled(data & StatusFlag::Pulse2.mask() != 0);
+                self.triangle.length.set_enabled(data & StatusFlag::Triangle.mask() != 0);
+                self.noise.length.set_enabled(data & StatusFlag::Noise.mask() != 0);
+                self.dmc.set_enabled(data & StatusFlag::DMC.mask() != 0);
+            },
+            0x4017 => {
+                self.five_step_mode = data & 0x80 != 0;
+                self.irq_inhibit = data & 0x40 != 0;
+                if self.irq_inhibit {
+                    self.frame_irq = false;
+                }
+                // the sequence restarts 3 or 4 CPU cycles later, depending on
+                // whether the write lands on an APU cycle
+                self.frame_reset_delay = Some(if self.cycles & 0x01 == 0 { 3 } else { 4 });
+            },
+            _ => {}
+        }
+    }
+
+    /**
+     * Reads $4015, which acknowledges the frame IRQ.
+     */
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_irq = false;
+        status
+    }
+
+    /**
+     * Reads $4015 without side effects. Bit 5 is open bus and left clear.
+     */
+    pub fn peek_status(&self) -> u8 {
+        let mut status = 0;
+        if self.pulse1.length.is_active() { status |= StatusFlag::Pulse1.mask(); }
+        if self.pulse2.length.is_active() { status |= StatusFlag::Pulse2.mask(); }
+        if self.triangle.length.is_active() { status |= StatusFlag::Triangle.mask(); }
+        if self.noise.length.is_active() { status |= StatusFlag::Noise.mask(); }
+        if self.dmc.bytes_remaining > 0 { status |= StatusFlag::DMC.mask(); }
+        if self.frame_irq { status |= StatusFlag::FrameInterrupt.mask(); }
+        if self.dmc.irq { status |= StatusFlag::DMCInterrupt.mask(); }
+        status
+    }
+
+    /**
+     * State of the APU IRQ line, true when asserted.
+     */
+    pub fn irq(&self) -> bool {
+        self.frame_irq || self.dmc.irq
+    }
+
+    /**
+     * Advances the APU by one CPU cycle.
+     */
+    pub fn clock(&mut self) {
+        self.clock_frame_counter();
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        // pulse timers run at the APU clock, every other CPU cycle
+        if self.cycles & 0x01 != 0 {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.cycles += 1;
+
+        let output = self.output();
+        if output != self.last_output {
+            self.resampler.add_delta(self.audio_cycles, output - self.last_output);
+            self.last_output = output;
+        }
+        self.audio_cycles += 1;
+        if self.audio_cycles >= MAX_AUDIO_FRAME_CYCLES {
+            self.end_audio_frame();
+        }
+    }
+
+    /**
+     * Resamples the output since the previous call, usually once per video
+     * frame. The samples are collected from the resampler.
+     */
+    pub fn end_audio_frame(&mut self) {
+        self.resampler.end_frame(self.audio_cycles);
+        self.audio_cycles = 0;
+    }
+
+    fn clock_frame_counter(&mut self) {
+        match self.frame_reset_delay {
+            Some(0) => {
+                self.frame_reset_delay = None;
+                self.frame_cycle = 0;
+                if self.five_step_mode {
+                    self.quarter_frame();
+                    self.half_frame();
+                }
+                return;
+            },
+            Some(delay) => self.frame_reset_delay = Some(delay - 1),
+            None => {}
+        }
+
+        self.frame_cycle += 1;
+        let [step1, step2, step3, step4, step5] = self.frame_steps;
+        match (self.frame_cycle, self.five_step_mode) {
+            (cycle, _) if cycle == step1 || cycle == step3 => self.quarter_frame(),
+            (cycle, _) if cycle == step2 => {
+                self.quarter_frame();
+                self.half_frame();
+            },
+            (cycle, false) if cycle == step4 - 1 => self.set_frame_irq(),
+            (cycle, false) if cycle == step4 => {
+                self.quarter_frame();
+                self.half_frame();
+                self.set_frame_irq();
+            },
+            (cycle, false) if cycle == step4 + 1 => {
+                self.set_frame_irq();
+                self.frame_cycle = 0;
+            },
+            (cycle, true) if cycle == step5 => {
+                self.quarter_frame();
+                self.half_frame();
+            },
+            (cycle, true) if cycle == step5 + 1 => self.frame_cycle = 0,
+            _ => {}
+        }
+    }
+
+    fn set_frame_irq(&mut self) {
+        if !self.irq_inhibit {
+            self.frame_irq = true;
+        }
+    }
+
+    fn quarter_frame(&mut self) {
+        self.pulse1.envelope.clock();
+        self.pulse2.envelope.clock();
+        self.triangle.clock_linear_counter();
+        self.noise.envelope.clock();
+    }
+
+    fn half_frame(&mut self) {
+        self.pulse1.length.clock();
+        self.pulse1.clock_sweep();
+        self.pulse2.length.clock();
+        self.pulse2.clock_sweep();
+        self.triangle.length.clock();
+        self.noise.length.clock();
+    }
+
+    /**
+     * Mixes the channel outputs with the non-linear DAC formulas, 0.0 to about 1.0.
+     */
+    pub fn output(&self) -> f32 {
+        mix(self.pulse1.output(), self.pulse2.output(), self.triangle.output(), self.noise.output(), self.dmc.output())
+    }
+}
+
+impl Default for APU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+ * Non-linear NES mixer, channel levels are 0-15 except DMC which is 0-127.
+ */
+pub fn mix(pulse1:u8, pulse2:u8, triangle:u8, noise:u8, dmc:u8) -> f32 {
+    let pulse_sum = (pulse1 + pulse2) as f32;
+    let pulse_out = if pulse_sum == 0.0 { 0.0 } else { 95.88 / (8128.0 / pulse_sum + 100.0) };
+    let tnd_sum = triangle as f32 / 8227.0 + noise as f32 / 12241.0 + dmc as f32 / 22638.0;
+    let tnd_out = if tnd_sum == 0.0 { 0.0 } else { 159.79 / (1.0 / tnd_sum + 100.0) };
+    pulse_out + tnd_out
+}
@@ -0,0 +1,175 @@
+use crate::nes::Region;
+
+/* Output periods in CPU cycles */
+const NTSC_RATE_TABLE:[u16; 16] = [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54];
+const PAL_RATE_TABLE:[u16; 16] = [398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50];
+
+/**
+ * Delta modulation channel, $4010-$4013. Plays 1 bit delta encoded samples
+ * from $C000-$FFFF, each bit moves the 7 bit output level up or down by 2.
+ * Sample bytes are fetched by DMA through the CPU bus whenever the sample
+ * buffer runs empty.
+ */
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug)]
+pub struct DMC {
+    pub irq_enabled:bool,
+    pub looping:bool,
+    rate_table:&'static [u16; 16],
+    rate_index:u8,
+    pub timer_period:u16,
+    timer:u16,
+    /* 7 bit output level, also loaded directly through $4011 */
+    pub level:u8,
+    sample_address:u16,
+    sample_length:u16,
+    current_address:u16,
+    pub bytes_remaining:u16,
+    sample_buffer:Option<u8>,
+    shift_register:u8,
+    bits_remaining:u8,
+    silence:bool,
+    pub irq:bool
+}
+
+impl DMC {
+    pub fn new() -> Self {
+        DMC {
+            irq_enabled: false,
+            looping: false,
+            rate_table: &NTSC_RATE_TABLE,
+            rate_index: 0,
+            timer_period: NTSC_RATE_TABLE[0],
+            timer: 0,
+            level: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+            irq: false
+        }
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.rate_table = match region {
+            Region::NTSC => &NTSC_RATE_TABLE,
+            Region::PAL => &PAL_RATE_TABLE
+        };
+        self.timer_period = self.rate_table[self.rate_index as usize];
+    }
+
+    /**
+     * Writes one of the channel registers, index is the address bits 0-1.
+     */
+    pub fn write_register(&mut self, index:u16, data:u8) {
+        match index {
+            0 => {
+                self.irq_enabled = data & 0x80 != 0;
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+                self.looping = data & 0x40 != 0;
+                self.rate_index = data & 0x0F;
+                self.timer_period = self.rate_table[self.rate_index as usize];
+            },
+            1 => self.level = data & 0x7F,
+            2 => self.sample_address = 0xC000 | ((data as u16) << 6),
+            _ => self.sample_length = ((data as u16) << 4) + 1
+        }
+    }
+
+    /**
+     * Enables the channel through $4015. Enabling restarts the sample only
+     * when the previous one has finished, disabling drops the rest of it.
+     */
+    pub fn set_enabled(&mut self, enabled:bool) {
+        self.irq = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    /**
+     * Address of the next sample byte when the buffer is waiting to be filled.
+     */
+    pub fn dma_request(&self) -> Option<u16> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Fills the sample buffer with the byte fetched for dma_request.
+     */
+    pub fn load_sample(&mut self, data:u8) {
+        self.sample_buffer = Some(data);
+        // the address wraps around to $8000
+        self.current_address = if self.current_address == 0xFFFF { 0x8000 } else { self.current_address + 1 };
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    /**
+     * Clocked every CPU cycle.
+     */
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period - 1;
+        if !self.silence {
+            if self.shift_register & 0x01 != 0 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift_register = sample;
+                },
+                None => self.silence = true
+            }
+        }
+    }
+
+    /**
+     * Current output level, 0-127.
+     */
+    pub fn output(&self) -> u8 {
+        self.level
+    }
+}
+
+impl Default for DMC {
+    fn default() -> Self {
+        Self::new()
+    }
+}
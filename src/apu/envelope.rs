@@ -0,0 +1,49 @@
+/**
+ * Volume envelope shared by the pulse and noise channels. Either outputs a
+ * constant volume or a decay level counting down from 15 once per period.
+ */
+#[derive(Debug, Default)]
+pub struct Envelope {
+    pub start:bool,
+    pub looping:bool,
+    pub constant_volume:bool,
+    /* Constant volume, or the divider period */
+    pub volume:u8,
+    divider:u8,
+    decay:u8
+}
+
+impl Envelope {
+    /**
+     * Loads the --LC VVVV bits of the channel control register.
+     */
+    pub fn write_control(&mut self, data:u8) {
+        self.looping = data & 0x20 != 0;
+        self.constant_volume = data & 0x10 != 0;
+        self.volume = data & 0x0F;
+    }
+
+    /**
+     * Clocked by quarter frames.
+     */
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant_volume { self.volume } else { self.decay }
+    }
+}
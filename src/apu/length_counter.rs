@@ -0,0 +1,48 @@
+const LENGTH_TABLE:[u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14,
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30
+];
+
+/**
+ * Silences a channel after a number of half frames unless halted.
+ */
+#[derive(Debug, Default)]
+pub struct LengthCounter {
+    pub enabled:bool,
+    pub halt:bool,
+    pub counter:u8
+}
+
+impl LengthCounter {
+    /**
+     * Loads the counter from the 5 bit table index, ignored while the channel is disabled.
+     */
+    pub fn load(&mut self, index:u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[(index & 0x1F) as usize];
+        }
+    }
+
+    /**
+     * Enables the channel through $4015, disabling clears the counter.
+     */
+    pub fn set_enabled(&mut self, enabled:bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    /**
+     * Clocked by half frames.
+     */
+    pub fn clock(&mut self) {
+        if !self.halt && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.counter > 0
+    }
+}
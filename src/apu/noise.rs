@@ -0,0 +1,99 @@
+use crate::apu::envelope::Envelope;
+use crate::apu::length_counter::LengthCounter;
+use crate::nes::Region;
+
+/* Timer periods in CPU cycles */
+const NTSC_PERIOD_TABLE:[u16; 16] = [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+const PAL_PERIOD_TABLE:[u16; 16] = [4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778];
+
+/**
+ * Noise channel, $400C-$400F. A 15 bit linear feedback shift register
+ * produces long pseudo-random sequences, or short 93 step ones in mode 1.
+ */
+#[derive(Debug)]
+pub struct Noise {
+    pub envelope:Envelope,
+    pub length:LengthCounter,
+    short_mode:bool,
+    period_table:&'static [u16; 16],
+    period_index:u8,
+    pub timer_period:u16,
+    timer:u16,
+    shift_register:u16
+}
+
+impl Noise {
+    pub fn new() -> Self {
+        Noise {
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+            short_mode: false,
+            period_table: &NTSC_PERIOD_TABLE,
+            period_index: 0,
+            timer_period: NTSC_PERIOD_TABLE[0],
+            timer: 0,
+            shift_register: 1
+        }
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.period_table = match region {
+            Region::NTSC => &NTSC_PERIOD_TABLE,
+            Region::PAL => &PAL_PERIOD_TABLE
+        };
+        self.timer_period = self.period_table[self.period_index as usize];
+    }
+
+    /**
+     * Writes one of the channel registers, index is the address bits 0-1.
+     */
+    pub fn write_register(&mut self, index:u16, data:u8) {
+        match index {
+            0 => {
+                self.length.halt = data & 0x20 != 0;
+                self.envelope.write_control(data);
+            },
+            1 => {},
+            2 => {
+                self.short_mode = data & 0x80 != 0;
+                self.period_index = data & 0x0F;
+                self.timer_period = self.period_table[self.period_index as usize];
+            },
+            _ => {
+                self.length.load(data >> 3);
+                self.envelope.start = true;
+            }
+        }
+    }
+
+    /**
+     * Clocked every CPU cycle.
+     */
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            let tap = if self.short_mode { 6 } else { 1 };
+            let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 0x01;
+            self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /**
+     * Current output level, 0-15.
+     */
+    pub fn output(&self) -> u8 {
+        if !self.length.is_active() || self.shift_register & 0x01 != 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self::new()
+    }
+}
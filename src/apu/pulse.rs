@@ -0,0 +1,138 @@
+use crate::apu::envelope::Envelope;
+use crate::apu::length_counter::LengthCounter;
+
+const DUTY_TABLE:[[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1]
+];
+
+/**
+ * Pulse channel, $4000-$4003 for pulse 1 and $4004-$4007 for pulse 2.
+ */
+#[derive(Debug)]
+pub struct Pulse {
+    pub envelope:Envelope,
+    pub length:LengthCounter,
+    /* Pulse 1 negates the sweep change with ones' complement, pulse 2 with two's complement */
+    ones_complement:bool,
+    duty:u8,
+    step:u8,
+    pub timer_period:u16,
+    timer:u16,
+    sweep_enabled:bool,
+    sweep_period:u8,
+    sweep_negate:bool,
+    sweep_shift:u8,
+    sweep_divider:u8,
+    sweep_reload:bool
+}
+
+impl Pulse {
+    /**
+     * Creates pulse 1 when ones_complement is set, pulse 2 otherwise.
+     */
+    pub fn new(ones_complement:bool) -> Self {
+        Pulse {
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+            ones_complement,
+            duty: 0,
+            step: 0,
+            timer_period: 0,
+            timer: 0,
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_divider: 0,
+            sweep_reload: false
+        }
+    }
+
+    /**
+     * Writes one of the four channel registers, index is the address bits 0-1.
+     */
+    pub fn write_register(&mut self, index:u16, data:u8) {
+        match index {
+            0 => {
+                self.duty = data >> 6;
+                self.length.halt = data & 0x20 != 0;
+                self.envelope.write_control(data);
+            },
+            1 => {
+                self.sweep_enabled = data & 0x80 != 0;
+                self.sweep_period = (data >> 4) & 0x07;
+                self.sweep_negate = data & 0x08 != 0;
+                self.sweep_shift = data & 0x07;
+                self.sweep_reload = true;
+            },
+            2 => self.timer_period = (self.timer_period & 0x0700) | data as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((data as u16 & 0x07) << 8);
+                self.length.load(data >> 3);
+                self.step = 0;
+                self.envelope.start = true;
+            }
+        }
+    }
+
+    /**
+     * Clocked every APU cycle, two CPU cycles.
+     */
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.step = (self.step + 1) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /**
+     * Period the sweep unit would set, continuously computed even while disabled.
+     */
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep_shift;
+        if self.sweep_negate {
+            let change = if self.ones_complement { change + 1 } else { change };
+            self.timer_period.saturating_sub(change)
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    /**
+     * The sweep unit mutes the channel for periods below 8 or targets above $7FF.
+     */
+    fn is_muted(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x07FF
+    }
+
+    /**
+     * Clocked by half frames.
+     */
+    pub fn clock_sweep(&mut self) {
+        if self.sweep_divider == 0 && self.sweep_enabled && self.sweep_shift > 0 && !self.is_muted() {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    /**
+     * Current output level, 0-15.
+     */
+    pub fn output(&self) -> u8 {
+        if !self.length.is_active() || self.is_muted() || DUTY_TABLE[self.duty as usize][self.step as usize] == 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use std::f64::consts::PI;
+use crate::apu::filter::{HighPassFilter, LowPassFilter};
+
+/* Kernel length in output samples */
+const TAPS:usize = 16;
+/* Sub-sample positions a step can be placed at */
+const PHASES:usize = 64;
+/* Kernel cutoff relative to the output sample rate, just below Nyquist */
+const CUTOFF:f64 = 0.45;
+/* Output filters of the NES, two high-pass stages and a low-pass stage */
+const HIGH_PASS_1_HZ:f32 = 90.0;
+const HIGH_PASS_2_HZ:f32 = 440.0;
+const LOW_PASS_HZ:f32 = 14000.0;
+/* Limit for dynamic rate control, as a fraction of the sample rate */
+const MAX_RATE_ADJUSTMENT:f64 = 0.01;
+
+/**
+ * Band-limited step synthesis. The APU output is a sum of steps, each change
+ * of level is added as a windowed sinc impulse at its exact position between
+ * two output samples. Integrating the impulses gives the band-limited
+ * waveform at the host sample rate without aliasing.
+ *
+ * Changes are added with add_delta during a frame, with times in source
+ * clocks since the frame start. end_frame turns the finished part into
+ * samples, run through the console's output filters.
+ */
+pub struct Resampler {
+    clock_rate:f64,
+    sample_rate:f64,
+    rate_adjustment:f64,
+    /* Output samples per source clock */
+    factor:f64,
+    /* Fractional sample position where the current frame starts */
+    time_offset:f64,
+    kernel:Vec<[f32; TAPS]>,
+    /* Impulses waiting to be integrated, indexed by output sample */
+    deltas:Vec<f32>,
+    integrator:f32,
+    high_pass_1:HighPassFilter,
+    high_pass_2:HighPassFilter,
+    low_pass:LowPassFilter,
+    samples:Vec<f32>
+}
+
+impl Resampler {
+    pub fn new(clock_rate:f64, sample_rate:f64) -> Self {
+        let mut resampler = Resampler {
+            clock_rate,
+            sample_rate,
+            rate_adjustment: 0.0,
+            factor: 0.0,
+            time_offset: 0.0,
+            kernel: build_kernel(),
+            deltas: vec![0.0; TAPS],
+            integrator: 0.0,
+            high_pass_1: HighPassFilter::new(HIGH_PASS_1_HZ, sample_rate as f32),
+            high_pass_2: HighPassFilter::new(HIGH_PASS_2_HZ, sample_rate as f32),
+            low_pass: LowPassFilter::new(LOW_PASS_HZ, sample_rate as f32),
+            samples: Vec::new()
+        };
+        resampler.update_factor();
+        resampler
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate:f64) {
+        self.sample_rate = sample_rate;
+        self.high_pass_1 = HighPassFilter::new(HIGH_PASS_1_HZ, sample_rate as f32);
+        self.high_pass_2 = HighPassFilter::new(HIGH_PASS_2_HZ, sample_rate as f32);
+        self.low_pass = LowPassFilter::new(LOW_PASS_HZ, sample_rate as f32);
+        self.update_factor();
+    }
+
+    pub fn set_clock_rate(&mut self, clock_rate:f64) {
+        self.clock_rate = clock_rate;
+        self.update_factor();
+    }
+
+    /**
+     * Stretches the output by a small ratio, e.g. 0.002 produces 0.2% more
+     * samples, so a frontend can keep its audio queue from draining or
+     * overflowing. Clamped to 1%.
+     */
+    pub fn set_rate_adjustment(&mut self, ratio:f64) {
+        self.rate_adjustment = ratio.clamp(-MAX_RATE_ADJUSTMENT, MAX_RATE_ADJUSTMENT);
+        self.update_factor();
+    }
+
+    fn update_factor(&mut self) {
+        self.factor = self.sample_rate * (1.0 + self.rate_adjustment) / self.clock_rate;
+    }
+
+    /**
+     * Adds a change of level at the source clock time since the frame start.
+     */
+    pub fn add_delta(&mut self, clock_time:u32, delta:f32) {
+        let position = self.time_offset + clock_time as f64 * self.factor;
+        let index = position as usize;
+        let phase = ((position - index as f64) * PHASES as f64) as usize;
+        if self.deltas.len() < index + TAPS {
+            self.deltas.resize(index + TAPS, 0.0);
+        }
+        for (tap, weight) in self.kernel[phase].iter().enumerate() {
+            self.deltas[index + tap] += weight * delta;
+        }
+    }
+
+    /**
+     * Ends the frame after the given number of source clocks and appends
+     * the completed samples to the output buffer.
+     */
+    pub fn end_frame(&mut self, clocks:u32) {
+        let end = self.time_offset + clocks as f64 * self.factor;
+        let count = end as usize;
+        if self.deltas.len() < count + TAPS {
+            self.deltas.resize(count + TAPS, 0.0);
+        }
+        for delta in self.deltas.drain(..count) {
+            self.integrator += delta;
+            let sample = self.high_pass_1.process(self.integrator);
+            let sample = self.high_pass_2.process(sample);
+            self.samples.push(self.low_pass.process(sample));
+        }
+        self.time_offset = end - count as f64;
+    }
+
+    /**
+     * Samples produced so far.
+     */
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /**
+     * Takes the samples produced so far, roughly -1.0 to 1.0.
+     */
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    /**
+     * Takes the samples produced so far as signed 16 bit PCM.
+     */
+    pub fn take_samples_i16(&mut self) -> Vec<i16> {
+        self.take_samples().iter()
+            .map(|sample| (sample * i16::MAX as f32).clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+            .collect()
+    }
+}
+
+/**
+ * Blackman windowed sinc impulses for each phase, every row sums to 1 so
+ * integrating a delta gives a step of the same height.
+ */
+fn build_kernel() -> Vec<[f32; TAPS]> {
+    (0..PHASES).map(|phase| {
+        let fraction = phase as f64 / PHASES as f64;
+        let mut row = [0.0; TAPS];
+        for (tap, weight) in row.iter_mut().enumerate() {
+            let x = tap as f64 - (TAPS / 2 - 1) as f64 - fraction;
+            let sinc = if x == 0.0 { 1.0 } else { (2.0 * PI * CUTOFF * x).sin() / (2.0 * PI * CUTOFF * x) };
+            let n = (x + (TAPS / 2) as f64) / TAPS as f64;
+            let window = 0.42 - 0.5 * (2.0 * PI * n).cos() + 0.08 * (4.0 * PI * n).cos();
+            *weight = sinc * window;
+        }
+        let sum:f64 = row.iter().sum();
+        row.map(|weight| (weight / sum) as f32)
+    }).collect()
+}
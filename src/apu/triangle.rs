@@ -0,0 +1,88 @@
+use crate::apu::length_counter::LengthCounter;
+
+const SEQUENCE:[u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15
+];
+
+/**
+ * Triangle channel, $4008-$400B. The timer runs at the CPU clock and the
+ * sequencer only advances while both the length and linear counters are
+ * non-zero.
+ */
+#[derive(Debug, Default)]
+pub struct Triangle {
+    pub length:LengthCounter,
+    /* Holds the sequencer for periods below 2 instead of producing the
+       ultrasonic tone, avoids pops that real TVs filter out anyway */
+    pub silence_ultrasonic:bool,
+    control:bool,
+    linear_reload_value:u8,
+    linear_counter:u8,
+    linear_reload:bool,
+    pub timer_period:u16,
+    timer:u16,
+    step:u8
+}
+
+impl Triangle {
+    pub fn new() -> Self {
+        Triangle { silence_ultrasonic: true, ..Default::default() }
+    }
+
+    /**
+     * Writes one of the channel registers, index is the address bits 0-1.
+     */
+    pub fn write_register(&mut self, index:u16, data:u8) {
+        match index {
+            0 => {
+                self.control = data & 0x80 != 0;
+                self.length.halt = self.control;
+                self.linear_reload_value = data & 0x7F;
+            },
+            1 => {},
+            2 => self.timer_period = (self.timer_period & 0x0700) | data as u16,
+            _ => {
+                self.timer_period = (self.timer_period & 0x00FF) | ((data as u16 & 0x07) << 8);
+                self.length.load(data >> 3);
+                self.linear_reload = true;
+            }
+        }
+    }
+
+    /**
+     * Clocked every CPU cycle.
+     */
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            let ultrasonic = self.silence_ultrasonic && self.timer_period < 2;
+            if self.length.is_active() && self.linear_counter > 0 && !ultrasonic {
+                self.step = (self.step + 1) & 0x1F;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /**
+     * Clocked by quarter frames.
+     */
+    pub fn clock_linear_counter(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    /**
+     * Current output level, 0-15. The triangle keeps its level when halted.
+     */
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.step as usize]
+    }
+}
@@ -0,0 +1,275 @@
+use crate::apu::APU;
+use crate::controller::{Button, Controller, StandardController};
+use crate::emu6502::bus::Bus;
+use crate::mapper::Mapper;
+use crate::mapper::ram_cartridge::RamCartridge;
+use crate::nes::Region;
+use crate::ppu::PPU;
+
+const RAM_SIZE:usize = 0x0800;
+const CARTRIDGE_START:u16 = 0x4020;
+/* Controller ports drive bits 0-4, the rest is open bus */
+const CONTROLLER_DATA_MASK:u8 = 0x1F;
+
+/**
+ * NES CPU memory map.
+ *
+ * $0000-$1FFF  2 KiB internal RAM, mirrored four times
+ * $2000-$3FFF  PPU registers, mirrored every 8 bytes
+ * $4000-$4017  APU and I/O registers, controllers at $4016/$4017
+ * $4018-$401F  Unused test mode registers
+ * $4020-$FFFF  Cartridge space
+ *
+ * Devices that are not attached yet behave as open bus: reads return the last
+ * value that was driven on the data bus and writes are dropped. Until a
+ * cartridge is inserted cartridge space is backed by a RamCartridge, so raw
+ * programs can still be loaded.
+ *
+ * Devices are clocked from a master clock counter, the PPU catches up with
+ * every CPU cycle by the region's divider ratio. The CPU ticks the bus before
+ * each of its accesses, so registers polled mid-instruction see the devices
+ * as of that cycle.
+ */
+pub struct NesBus {
+    pub ram:[u8; RAM_SIZE],
+    pub ppu:PPU,
+    pub apu:APU,
+    pub mapper:Box<dyn Mapper>,
+    /* Devices plugged into controller ports 1 and 2 */
+    pub controllers:[Option<Box<dyn Controller>>; 2],
+    region:Region,
+    /* Master clock ticks elapsed, and those the PPU has been clocked for */
+    master_clock:u64,
+    ppu_master_clock:u64,
+    /* Last value driven on the data bus */
+    open_bus:u8,
+    /* Page written to $4014, waiting for the CPU to copy it */
+    oam_dma_page:Option<u8>
+}
+
+impl NesBus {
+    pub fn new() -> Self {
+        NesBus {
+            ram: [0; RAM_SIZE],
+            ppu: PPU::new(),
+            apu: APU::new(),
+            mapper: Box::new(RamCartridge::new()),
+            controllers: [Some(Box::new(StandardController::new())), Some(Box::new(StandardController::new()))],
+            region: Region::NTSC,
+            master_clock: 0,
+            ppu_master_clock: 0,
+            open_bus: 0,
+            oam_dma_page: None
+        }
+    }
+
+    /**
+     * Connects the cartridge board to cartridge space and the PPU.
+     */
+    pub fn insert_cartridge(&mut self, mapper: Box<dyn Mapper>) {
+        self.mapper = mapper;
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /**
+     * Switches the PPU and APU timing to the console region.
+     */
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu.region = region;
+        self.apu.set_region(region);
+    }
+
+    /**
+     * Master clock ticks elapsed since power on.
+     */
+    pub fn master_clock(&self) -> u64 {
+        self.master_clock
+    }
+
+    /**
+     * Plugs a device into controller port 0 or 1, None leaves it empty.
+     */
+    pub fn connect_controller(&mut self, port:usize, controller: Option<Box<dyn Controller>>) {
+        self.controllers[port] = controller;
+    }
+
+    /**
+     * Presses or releases a button on the device in controller port 0 or 1.
+     */
+    pub fn set_button(&mut self, port:usize, button:Button, pressed:bool) {
+        if let Some(controller) = self.controllers[port].as_mut() {
+            controller.set_button(button, pressed);
+        }
+    }
+
+    fn read_controller(&mut self, port:usize) -> u8 {
+        let data = self.controllers[port].as_mut().map_or(0, |controller| controller.read());
+        (self.open_bus & !CONTROLLER_DATA_MASK) | (data & CONTROLLER_DATA_MASK)
+    }
+
+    fn peek_controller(&self, port:usize) -> u8 {
+        let data = self.controllers[port].as_ref().map_or(0, |controller| controller.peek());
+        (self.open_bus & !CONTROLLER_DATA_MASK) | (data & CONTROLLER_DATA_MASK)
+    }
+
+    /**
+     * Advances the devices by one CPU cycle.
+     */
+    fn clock(&mut self) {
+        self.master_clock += self.region.cpu_divider();
+        self.mapper.cpu_clock(1);
+        self.apu.clock();
+        let ppu_divider = self.region.ppu_divider();
+        while self.ppu_master_clock + ppu_divider <= self.master_clock {
+            self.ppu_master_clock += ppu_divider;
+            self.ppu.clock(self.mapper.as_mut());
+        }
+    }
+
+    /**
+     * Whether an odd number of CPU cycles has been clocked, DMA reads are
+     * aligned to odd cycles.
+     */
+    fn is_odd_cycle(&self) -> bool {
+        (self.master_clock / self.region.cpu_divider()) & 0x01 != 0
+    }
+
+    /**
+     * Fetches a DMC sample byte while the CPU is halted on a read. A dummy
+     * cycle follows the halt, and one more aligns the fetch: 3 or 4 cycles.
+     * The halted read reaches the bus, so the controller and PPU data ports
+     * see one read more than the CPU makes.
+     */
+    fn dmc_dma(&mut self, halted:u16, sample_addr:u16) -> u16 {
+        self.clock();
+        self.read(halted);
+        self.clock();
+        let cycles = if self.is_odd_cycle() { 3 } else { 4 };
+        if cycles == 4 {
+            self.clock();
+        }
+        self.clock();
+        let data = self.read(sample_addr);
+        self.apu.dmc.load_sample(data);
+        cycles
+    }
+
+    /**
+     * Copies a page to the PPU OAM through $2004 while the CPU is halted on
+     * a read. One more cycle after the halt aligns the first read when
+     * needed, then 256 reads and writes alternate: 513 or 514 cycles. A DMC
+     * sample fetch that comes due meanwhile takes a read slot and adds 2.
+     */
+    fn oam_dma(&mut self, halted:u16, page:u8) -> u16 {
+        self.clock();
+        self.read(halted);
+        let mut cycles = if self.is_odd_cycle() { 1 } else { 2 };
+        if cycles == 2 {
+            self.clock();
+        }
+        for offset in 0..=0xFF {
+            if let Some(sample_addr) = self.apu.dmc.dma_request() {
+                self.clock();
+                let data = self.read(sample_addr);
+                self.apu.dmc.load_sample(data);
+                // the copy realigns its read
+                self.clock();
+                cycles += 2;
+            }
+            self.clock();
+            let data = self.read((page as u16) << 8 | offset);
+            self.clock();
+            self.write(0x2004, data);
+            cycles += 2;
+        }
+        cycles
+    }
+
+    /**
+     * Last value seen on the data bus, returned by unmapped reads.
+     */
+    pub fn open_bus(&self) -> u8 {
+        self.open_bus
+    }
+}
+
+impl Default for NesBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus for NesBus {
+    fn read(&mut self, addr:u16) -> u8 {
+        let data = match addr {
+            0x2000..=0x3FFF => self.ppu.read_register(addr, self.mapper.as_mut()),
+            0x4015 => self.apu.read_status() | (self.open_bus & 0x20),
+            0x4016 => self.read_controller(0),
+            0x4017 => self.read_controller(1),
+            CARTRIDGE_START..=0xFFFF => self.mapper.cpu_read(addr).unwrap_or(self.open_bus),
+            _ => self.peek(addr)
+        };
+        self.open_bus = data;
+        data
+    }
+
+    fn write(&mut self, addr:u16, data:u8) {
+        self.open_bus = data;
+        match addr {
+            0x0000..=0x1FFF => self.ram[addr as usize & (RAM_SIZE - 1)] = data,
+            0x2000..=0x3FFF => self.ppu.write_register(addr, data, self.mapper.as_mut()),
+            0x4014 => self.oam_dma_page = Some(data),
+            0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.write_register(addr, data),
+            0x4016 => {
+                for controller in self.controllers.iter_mut().flatten() {
+                    controller.write_strobe(data & 0x01 != 0);
+                }
+            },
+            // test mode registers
+            0x4000..=0x401F => {},
+            CARTRIDGE_START..=0xFFFF => self.mapper.cpu_write(addr, data)
+        }
+    }
+
+    fn peek(&self, addr:u16) -> u8 {
+        match addr {
+            0x0000..=0x1FFF => self.ram[addr as usize & (RAM_SIZE - 1)],
+            0x2000..=0x3FFF => self.ppu.peek_register(addr),
+            0x4015 => self.apu.peek_status() | (self.open_bus & 0x20),
+            0x4016 => self.peek_controller(0),
+            0x4017 => self.peek_controller(1),
+            0x4000..=0x401F => self.open_bus,
+            CARTRIDGE_START..=0xFFFF => self.mapper.cpu_peek(addr).unwrap_or(self.open_bus)
+        }
+    }
+
+    fn tick(&mut self, cycles:u8) {
+        for _ in 0..cycles {
+            self.clock();
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.mapper.irq() || self.apu.irq()
+    }
+
+    fn nmi(&self) -> bool {
+        self.ppu.nmi()
+    }
+
+    fn halt(&mut self, addr:u16) -> u16 {
+        let mut cycles = 0;
+        if let Some(page) = self.oam_dma_page.take() {
+            cycles += self.oam_dma(addr, page);
+        }
+        // a sample fetch due at the end of the copy keeps the CPU halted
+        if let Some(sample_addr) = self.apu.dmc.dma_request() {
+            cycles += self.dmc_dma(addr, sample_addr);
+        }
+        cycles
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+const HEADER_SIZE:usize = 16;
+const TRAINER_SIZE:usize = 512;
+const PRG_ROM_BANK_SIZE:usize = 0x4000;
+const CHR_ROM_BANK_SIZE:usize = 0x2000;
+const NES_MAGIC:[u8; 4] = [b'N', b'E', b'S', 0x1A];
+
+/**
+ * Nametable mirroring arrangement.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    SingleScreenLower,
+    SingleScreenUpper,
+    FourScreen
+}
+
+/**
+ * Header format of the ROM file.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomFormat {
+    INes,
+    Nes20
+}
+
+/**
+ * CPU/PPU timing the cartridge was made for.
+ */
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    NTSC,
+    PAL,
+    MultiRegion,
+    Dendy
+}
+
+/**
+ * Console the cartridge was made for.
+ */
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    NES,
+    VsSystem,
+    Playchoice10,
+    /* Extended console type from NES 2.0 byte 13 */
+    Extended(u8)
+}
+
+/**
+ * Errors reported while loading a ROM file.
+ */
+#[derive(Debug)]
+pub enum CartridgeError {
+    /* File could not be read */
+    Io(std::io::Error),
+    /* File is shorter than the 16 byte header */
+    TruncatedHeader { len:usize },
+    /* File does not start with "NES<EOF>" */
+    InvalidMagic([u8; 4]),
+    /* Header declares no PRG ROM */
+    MissingPrgRom,
+    /* NES 2.0 exponent-multiplier size does not fit in memory */
+    RomSizeOverflow,
+    /* No mapper implementation for the board */
+    UnsupportedMapper(u16),
+    TruncatedTrainer { expected:usize, available:usize },
+    TruncatedPrgRom { expected:usize, available:usize },
+    TruncatedChrRom { expected:usize, available:usize }
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::Io(err) => write!(f, "cannot read ROM file: {}", err),
+            CartridgeError::TruncatedHeader { len } =>
+                write!(f, "file is {} bytes, shorter than the {} byte header", len, HEADER_SIZE),
+            CartridgeError::InvalidMagic(magic) =>
+                write!(f, "invalid magic {:02X?}, expected {:02X?}", magic, NES_MAGIC),
+            CartridgeError::MissingPrgRom => write!(f, "header declares no PRG ROM"),
+            CartridgeError::RomSizeOverflow => write!(f, "ROM size in the header is too large"),
+            CartridgeError::UnsupportedMapper(mapper) => write!(f, "mapper {} is not supported", mapper),
+            CartridgeError::TruncatedTrainer { expected, available } =>
+                write!(f, "trainer truncated: expected {} bytes, {} available", expected, available),
+            CartridgeError::TruncatedPrgRom { expected, available } =>
+                write!(f, "PRG ROM truncated: expected {} bytes, {} available", expected, available),
+            CartridgeError::TruncatedChrRom { expected, available } =>
+                write!(f, "CHR ROM truncated: expected {} bytes, {} available", expected, available)
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CartridgeError::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<std::io::Error> for CartridgeError {
+    fn from(err: std::io::Error) -> Self {
+        CartridgeError::Io(err)
+    }
+}
+
+/**
+ * Contents of an iNES or NES 2.0 ROM file.
+ */
+#[derive(Debug, Clone)]
+pub struct Cartridge {
+    pub format:RomFormat,
+    pub mapper:u16,
+    pub submapper:u8,
+    pub mirroring:Mirroring,
+    /* Battery backed PRG RAM or other persistent memory is present */
+    pub battery:bool,
+    /* 512 byte trainer, loaded at $7000 */
+    pub trainer:Option<Vec<u8>>,
+    pub prg_rom:Vec<u8>,
+    /* Empty when the board uses CHR RAM */
+    pub chr_rom:Vec<u8>,
+    pub prg_ram_size:usize,
+    pub prg_nvram_size:usize,
+    pub chr_ram_size:usize,
+    pub chr_nvram_size:usize,
+    pub timing:Timing,
+    pub console_type:ConsoleType
+}
+
+impl Cartridge {
+    /**
+     * Loads a .nes file from disk.
+     */
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Cartridge, CartridgeError> {
+        let data = fs::read(path)?;
+        Cartridge::from_bytes(&data)
+    }
+
+    /**
+     * Parses the contents of a .nes file.
+     */
+    pub fn from_bytes(data: &[u8]) -> Result<Cartridge, CartridgeError> {
+        if data.len() < HEADER_SIZE {
+            return Err(CartridgeError::TruncatedHeader { len: data.len() });
+        }
+        let header = &data[..HEADER_SIZE];
+        if header[0..4] != NES_MAGIC {
+            let mut magic = [0; 4];
+            magic.copy_from_slice(&header[0..4]);
+            return Err(CartridgeError::InvalidMagic(magic));
+        }
+
+        let flags6 = header[6];
+        let flags7 = header[7];
+        let format = if flags7 & 0x0C == 0x08 { RomFormat::Nes20 } else { RomFormat::INes };
+
+        let mirroring = if flags6 & 0x08 != 0 {
+            Mirroring::FourScreen
+        } else if flags6 & 0x01 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let battery = flags6 & 0x02 != 0;
+        let has_trainer = flags6 & 0x04 != 0;
+
+        let (mut cartridge, prg_rom_size, chr_rom_size) = match format {
+            RomFormat::Nes20 => Cartridge::parse_nes20_header(header)?,
+            RomFormat::INes => Cartridge::parse_ines_header(header)
+        };
+        cartridge.mirroring = mirroring;
+        cartridge.battery = battery;
+
+        if prg_rom_size == 0 {
+            return Err(CartridgeError::MissingPrgRom);
+        }
+
+        let mut offset = HEADER_SIZE;
+        if has_trainer {
+            let available = data.len() - offset;
+            if available < TRAINER_SIZE {
+                return Err(CartridgeError::TruncatedTrainer { expected: TRAINER_SIZE, available });
+            }
+            cartridge.trainer = Some(data[offset .. offset + TRAINER_SIZE].to_vec());
+            offset += TRAINER_SIZE;
+        }
+
+        let available = data.len() - offset;
+        if available < prg_rom_size {
+            return Err(CartridgeError::TruncatedPrgRom { expected: prg_rom_size, available });
+        }
+        cartridge.prg_rom = data[offset .. offset + prg_rom_size].to_vec();
+        offset += prg_rom_size;
+
+        let available = data.len() - offset;
+        if available < chr_rom_size {
+            return Err(CartridgeError::TruncatedChrRom { expected: chr_rom_size, available });
+        }
+        cartridge.chr_rom = data[offset .. offset + chr_rom_size].to_vec();
+
+        Ok(cartridge)
+    }
+
+    /**
+     * Board uses CHR RAM instead of CHR ROM.
+     */
+    pub fn has_chr_ram(&self) -> bool {
+        self.chr_rom.is_empty()
+    }
+
+    /**
+     * Parses an iNES 1.0 header, returns the cartridge without ROM contents
+     * along with the PRG and CHR ROM sizes.
+     */
+    fn parse_ines_header(header: &[u8]) -> (Cartridge, usize, usize) {
+        // Old dumping tools wrote garbage such as "DiskDude!" into bytes 7-15,
+        // the upper mapper nibble is only trusted when the tail of the header is clean.
+        let clean_tail = header[12..16].iter().all(|byte| *byte == 0);
+        let mapper_high = if clean_tail { header[7] & 0xF0 } else { 0 };
+        let mapper = (mapper_high | (header[6] >> 4)) as u16;
+
+        let console_type = if !clean_tail {
+            ConsoleType::NES
+        } else if header[7] & 0x01 != 0 {
+            ConsoleType::VsSystem
+        } else if header[7] & 0x02 != 0 {
+            ConsoleType::Playchoice10
+        } else {
+            ConsoleType::NES
+        };
+        let timing = if clean_tail && header[9] & 0x01 != 0 { Timing::PAL } else { Timing::NTSC };
+
+        let chr_rom_size = header[5] as usize * CHR_ROM_BANK_SIZE;
+        // A zero PRG RAM size means 8 KiB for compatibility
+        let prg_ram_banks = if clean_tail { header[8].max(1) } else { 1 };
+
+        let cartridge = Cartridge {
+            format: RomFormat::INes,
+            mapper,
+            submapper: 0,
+            mirroring: Mirroring::Horizontal,
+            battery: false,
+            trainer: None,
+            prg_rom: Vec::new(),
+            chr_rom: Vec::new(),
+            prg_ram_size: prg_ram_banks as usize * 0x2000,
+            prg_nvram_size: 0,
+            chr_ram_size: if chr_rom_size == 0 { CHR_ROM_BANK_SIZE } else { 0 },
+            chr_nvram_size: 0,
+            timing,
+            console_type
+        };
+        (cartridge, header[4] as usize * PRG_ROM_BANK_SIZE, chr_rom_size)
+    }
+
+    /**
+     * Parses a NES 2.0 header, returns the cartridge without ROM contents
+     * along with the PRG and CHR ROM sizes.
+     */
+    fn parse_nes20_header(header: &[u8]) -> Result<(Cartridge, usize, usize), CartridgeError> {
+        let mapper = ((header[8] as u16 & 0x0F) << 8)
+            | (header[7] as u16 & 0xF0)
+            | (header[6] as u16 >> 4);
+        let submapper = header[8] >> 4;
+
+        let prg_rom_size = nes20_rom_size(header[4], header[9] & 0x0F, PRG_ROM_BANK_SIZE)?;
+        let chr_rom_size = nes20_rom_size(header[5], header[9] >> 4, CHR_ROM_BANK_SIZE)?;
+
+        let timing = match header[12] & 0x03 {
+            0 => Timing::NTSC,
+            1 => Timing::PAL,
+            2 => Timing::MultiRegion,
+            _ => Timing::Dendy
+        };
+        let console_type = match header[7] & 0x03 {
+            0 => ConsoleType::NES,
+            1 => ConsoleType::VsSystem,
+            2 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Extended(header[13] & 0x0F)
+        };
+
+        let cartridge = Cartridge {
+            format: RomFormat::Nes20,
+            mapper,
+            submapper,
+            mirroring: Mirroring::Horizontal,
+            battery: false,
+            trainer: None,
+            prg_rom: Vec::new(),
+            chr_rom: Vec::new(),
+            prg_ram_size: nes20_ram_size(header[10] & 0x0F),
+            prg_nvram_size: nes20_ram_size(header[10] >> 4),
+            chr_ram_size: nes20_ram_size(header[11] & 0x0F),
+            chr_nvram_size: nes20_ram_size(header[11] >> 4),
+            timing,
+            console_type
+        };
+        Ok((cartridge, prg_rom_size, chr_rom_size))
+    }
+}
+
+/**
+ * NES 2.0 ROM size. When the MSB nibble is $F the LSB byte is an
+ * exponent-multiplier pair EEEEEEMM giving 2^E * (MM * 2 + 1) bytes.
+ */
+fn nes20_rom_size(lsb:u8, msb:u8, bank_size:usize) -> Result<usize, CartridgeError> {
+    if msb == 0x0F {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0x03) as usize * 2 + 1;
+        1usize.checked_shl(exponent)
+            .filter(|size| size.leading_zeros() >= 3)
+            .map(|size| size * multiplier)
+            .ok_or(CartridgeError::RomSizeOverflow)
+    } else {
+        Ok((((msb as usize) << 8) | lsb as usize) * bank_size)
+    }
+}
+
+/**
+ * NES 2.0 RAM size, a shift count of 0 means no RAM, otherwise 64 << shift bytes.
+ */
+fn nes20_ram_size(shift:u8) -> usize {
+    if shift == 0 { 0 } else { 64 << shift }
+}
@@ -0,0 +1,109 @@
+/**
+ * Standard controller buttons, in the order they are shifted out.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right
+}
+
+impl Button {
+    pub fn mask(&self) -> u8 {
+        match self {
+            Button::A => 0b0000_0001,
+            Button::B => 0b0000_0010,
+            Button::Select => 0b0000_0100,
+            Button::Start => 0b0000_1000,
+            Button::Up => 0b0001_0000,
+            Button::Down => 0b0010_0000,
+            Button::Left => 0b0100_0000,
+            Button::Right => 0b1000_0000
+        }
+    }
+}
+
+/**
+ * Input device plugged into one of the two controller ports. Writes to
+ * $4016 drive the strobe line of both ports, reads of $4016 and $4017 return
+ * data lines D0-D4 of port 1 and port 2.
+ */
+pub trait Controller {
+    /**
+     * Sets the strobe line, bit 0 of a $4016 write.
+     */
+    fn write_strobe(&mut self, strobe:bool);
+
+    /**
+     * Reads the data lines, the CPU sees bits 0-4.
+     */
+    fn read(&mut self) -> u8;
+
+    /**
+     * Reads the data lines without side effects.
+     */
+    fn peek(&self) -> u8;
+
+    /**
+     * Presses or releases a button, ignored by devices that have none.
+     */
+    fn set_button(&mut self, _button:Button, _pressed:bool) {}
+}
+
+/**
+ * Standard joypad. The strobe loads the button states into an 8 bit shift
+ * register which is then read out one bit per read, A first. Further reads
+ * return 1 as the register fills up with ones.
+ */
+#[derive(Debug, Default)]
+pub struct StandardController {
+    /* Pressed buttons, see Button::mask */
+    pub buttons:u8,
+    strobe:bool,
+    shift_register:u8
+}
+
+impl StandardController {
+    pub fn new() -> Self {
+        StandardController::default()
+    }
+}
+
+impl Controller for StandardController {
+    fn write_strobe(&mut self, strobe:bool) {
+        // the register reloads while strobe is high and keeps the last state when it falls
+        if self.strobe || strobe {
+            self.shift_register = self.buttons;
+        }
+        self.strobe = strobe;
+    }
+
+    fn read(&mut self) -> u8 {
+        let data = self.peek();
+        if !self.strobe {
+            self.shift_register = (self.shift_register >> 1) | 0x80;
+        }
+        data
+    }
+
+    fn peek(&self) -> u8 {
+        if self.strobe {
+            self.buttons & Button::A.mask()
+        } else {
+            self.shift_register & 0x01
+        }
+    }
+
+    fn set_button(&mut self, button:Button, pressed:bool) {
+        if pressed {
+            self.buttons |= button.mask();
+        } else {
+            self.buttons &= !button.mask();
+        }
+    }
+}
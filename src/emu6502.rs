@@ -1,3 +1,7 @@
+pub mod bus;
 pub mod cpu;
 pub mod ram;
-pub mod op_codes;
\ No newline at end of file
+pub mod op_codes;
+pub mod loader;
+pub mod disassembler;
+pub mod trace;
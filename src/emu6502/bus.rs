@@ -0,0 +1,79 @@
+/**
+ * Memory map seen by the CPU. Every read and write the CPU makes goes through
+ * this trait, so an implementation can route addresses to RAM, I/O registers
+ * or cartridge hardware.
+ */
+pub trait Bus {
+    /**
+     * Reads a byte, reads may have side effects on I/O registers.
+     */
+    fn read(&mut self, addr:u16) -> u8;
+
+    /**
+     * Writes a byte to the address.
+     */
+    fn write(&mut self, addr:u16, data:u8);
+
+    /**
+     * Reads a byte without side effects, used by debuggers and tracing.
+     */
+    fn peek(&self, addr:u16) -> u8;
+
+    /**
+     * Lets devices on the bus keep up with the CPU. Called with one cycle
+     * before each read or write the CPU makes, then with the cycles of the
+     * instruction, interrupt or reset that did not access the bus.
+     */
+    fn tick(&mut self, _cycles:u8) {}
+
+    /**
+     * State of the IRQ line driven by devices on the bus, true when asserted.
+     */
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /**
+     * State of the NMI line driven by devices on the bus, true when asserted.
+     */
+    fn nmi(&self) -> bool {
+        false
+    }
+
+    /**
+     * Called before every CPU read. A device that needs the bus, such as a
+     * DMA unit, can pull RDY low and halt the CPU there: the bus clocks its
+     * devices through the halted cycles itself, the halted read of addr
+     * included, and returns how many there were. The CPU makes the read
+     * once it resumes.
+     */
+    fn halt(&mut self, _addr:u16) -> u16 {
+        0
+    }
+
+    /**
+     * Reads a little endian word, the high byte comes from the next address.
+     */
+    fn read_word(&mut self, addr:u16) -> u16 {
+        let lo = self.read(addr) as u16;
+        let hi = self.read(addr.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    /**
+     * Writes a little endian word.
+     */
+    fn write_word(&mut self, addr:u16, data:u16) {
+        self.write(addr, (data & 0x00FF) as u8);
+        self.write(addr.wrapping_add(1), (data >> 8) as u8);
+    }
+
+    /**
+     * Reads a little endian word without side effects.
+     */
+    fn peek_word(&self, addr:u16) -> u16 {
+        let lo = self.peek(addr) as u16;
+        let hi = self.peek(addr.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+}
@@ -1,9 +1,105 @@
-use std::u8;
-
+use crate::emu6502::bus::Bus;
+use crate::emu6502::loader::Image;
 use crate::emu6502::ram::RAM;
 use crate::emu6502::op_codes::OpCode;
 use crate::emu6502::op_codes::OpCodeMap;
 use crate::emu6502::op_codes::AddressingMode;
+use crate::emu6502::op_codes::Instruction;
+use crate::emu6502::trace::{Diagnostic, TraceEvent, Tracer};
+
+pub const NMI_VECTOR:u16 = 0xFFFA;
+pub const RESET_VECTOR:u16 = 0xFFFC;
+pub const IRQ_VECTOR:u16 = 0xFFFE;
+
+/* Cycles taken by the hardware interrupt sequence */
+const INTERRUPT_CYCLES:u8 = 7;
+
+/**
+ * Hardware interrupts serviced by the CPU between instructions.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum Interrupt {
+    NMI,
+    IRQ,
+}
+
+/* Magic constant used for the unstable XAA and LXA opcodes */
+const UNSTABLE_MAGIC:u8 = 0xEE;
+
+/**
+ * CPU models that differ in behaviour. The Ricoh 2A03 used in the NES is
+ * a NMOS 6502 with the decimal mode disconnected.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CPUVariant {
+    NMOS6502,
+    RP2A03,
+}
+
+/**
+ * Outcome of executing a single instruction with CPU::step.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /* The instruction completed normally */
+    Executed { cycles: u8 },
+    /* A BRK instruction was executed */
+    Break { cycles: u8 },
+    /* A pending NMI or IRQ was serviced instead of an instruction */
+    Interrupt { kind: Interrupt, cycles: u8 },
+    /* The CPU was halted by a DMA transfer or CPU::stall */
+    Stalled { cycles: u16 },
+    /* The CPU is halted by a KIL/JAM opcode and will not execute further */
+    Jammed,
+    /* The opcode is not supported, program counter still points to it */
+    IllegalOpcode(u8),
+}
+
+impl StepResult {
+    /**
+     * Number of cycles consumed by the step, zero if nothing was executed.
+     */
+    pub fn cycles(&self) -> u16 {
+        match self {
+            StepResult::Executed { cycles }
+            | StepResult::Break { cycles }
+            | StepResult::Interrupt { cycles, .. } => *cycles as u16,
+            StepResult::Stalled { cycles } => *cycles,
+            StepResult::Jammed | StepResult::IllegalOpcode(_) => 0,
+        }
+    }
+}
+
+/**
+ * Conditions for CPU::run_until to return.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopCondition {
+    /* Stop after a BRK instruction was executed */
+    Break,
+    /* Stop when the program counter reaches the address, before executing it */
+    ProgramCounter(u16),
+    /* Stop when the next instruction has this opcode, before executing it */
+    Opcode(u8),
+    /* Stop once at least this many cycles have run */
+    Cycles(u64),
+    /* Stop after this many instructions, interrupt sequences do not count */
+    Instructions(u64),
+    /* Stop when an instruction jumps or branches to itself, the way test suites end */
+    Trap,
+}
+
+/**
+ * Reason CPU::run_until returned.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /* The requested stop condition was met */
+    ConditionMet,
+    Jammed,
+    IllegalOpcode(u8),
+}
 
 #[derive(Debug)]
 #[allow(non_camel_case_types)]
@@ -19,32 +115,104 @@ pub enum CPUFlag {
     Negative
 }
 
-pub struct CPU {
+impl CPUFlag {
+    /**
+     * Returns the bit mask of the flag in the status register.
+     */
+    pub fn mask(&self) -> u8 {
+        match self {
+            CPUFlag::Carry =>               0b0000_0001,
+            CPUFlag::Zero =>                0b0000_0010,
+            CPUFlag::InterruptDisable =>    0b0000_0100,
+            CPUFlag::Decimal =>             0b0000_1000,
+            CPUFlag::B0 =>                  0b0001_0000,
+            CPUFlag::B1 =>                  0b0010_0000,
+            CPUFlag::Overflow =>            0b0100_0000,
+            CPUFlag::Negative =>            0b1000_0000
+        }
+    }
+}
+
+pub struct CPU<B: Bus = RAM> {
     pub register_a:u8,
     pub register_x:u8,
     pub register_y:u8,
     pub program_counter:u16,
     pub stack_pointer:u8,
     pub status:u8,
-    pub memory:RAM,
-    pub op_codes:OpCodeMap
+    pub memory:B,
+    pub op_codes:OpCodeMap,
+    /* Total number of cycles executed since power on */
+    pub cycles:u64,
+    pub variant:CPUVariant,
+    /* Undocumented opcodes are executed, otherwise they are reported as illegal */
+    pub unofficial_opcodes:bool,
+    /* Set by a KIL/JAM opcode, only a reset recovers the CPU */
+    pub jammed:bool,
+    /* Set by resolve_operand_addr when indexing crossed a page */
+    page_crossed:bool,
+    /* Cycles added by the instruction itself, such as taken branches */
+    extra_cycles:u8,
+    /* Interrupt inputs, NMI is edge triggered and IRQ is level triggered */
+    nmi_line:bool,
+    irq_line:bool,
+    /* NMI line driven by devices on the bus, such as the PPU */
+    bus_nmi_line:bool,
+    /* NMI edge seen since the last interrupt poll */
+    nmi_detected:bool,
+    /* Interrupts found by the poll at the end of the last instruction */
+    nmi_pending:bool,
+    irq_pending:bool,
+    /* Cycles the CPU is halted for before the next instruction */
+    stall_cycles:u16,
+    /* Bus accesses since the last tick, each has clocked its own cycle */
+    access_cycles:u8,
+    /* Cycles the bus halted the CPU for that no step has reported yet */
+    halted_cycles:u16,
+    /* Receives instruction events and diagnostics when set */
+    pub tracer:Option<Box<dyn Tracer>>
 }
 
-/* Core CPU functionality */
 impl CPU {
     /**
-     * CPU constructor, create a new CPU object
+     * CPU constructor, create a new CPU object with a flat 64 KiB RAM
      */
     pub fn new() -> Self {
+        CPU::with_bus(RAM::new())
+    }
+}
+
+/* Core CPU functionality */
+impl<B: Bus> CPU<B> {
+    /**
+     * Creates a CPU connected to the given memory map
+     */
+    pub fn with_bus(bus: B) -> Self {
         CPU {
             register_a: 0,
             register_x: 0,
             register_y: 0,
             program_counter: 0,
             stack_pointer: 0,
-            status:0, 
-            memory: RAM::new(),
-            op_codes: OpCodeMap::new()
+            status:0,
+            memory: bus,
+            op_codes: OpCodeMap::new(),
+            cycles: 0,
+            variant: CPUVariant::RP2A03,
+            unofficial_opcodes: true,
+            jammed: false,
+            page_crossed: false,
+            extra_cycles: 0,
+            nmi_line: false,
+            bus_nmi_line: false,
+            irq_line: false,
+            nmi_detected: false,
+            nmi_pending: false,
+            irq_pending: false,
+            stall_cycles: 0,
+            access_cycles: 0,
+            halted_cycles: 0,
+            tracer: None
         }
     }
 
@@ -54,110 +222,492 @@ impl CPU {
     pub fn reset(&mut self) {
         self.register_a = 0;
         self.register_x = 0;
-        self.status = 0;
-        self.program_counter = self.memory.read_word(0xFFFC);
-        self.stack_pointer = 0xff;
+        self.status = CPUFlag::InterruptDisable.mask() | CPUFlag::B1.mask();
+        self.program_counter = self.read_word(RESET_VECTOR);
+        // reset runs the interrupt sequence with the stack writes suppressed
+        self.stack_pointer = 0xfd;
+        self.cycles += INTERRUPT_CYCLES as u64;
+        self.tick(INTERRUPT_CYCLES);
+        self.nmi_detected = false;
+        self.nmi_pending = false;
+        self.irq_pending = false;
+        self.stall_cycles = 0;
+        self.halted_cycles = 0;
+        self.jammed = false;
+    }
+
+    /**
+     * Drives the NMI input. The interrupt is triggered on the transition
+     * from inactive to active, holding the line active does not retrigger it.
+     */
+    pub fn set_nmi_line(&mut self, active: bool) {
+        if active && !self.nmi_line {
+            self.nmi_detected = true;
+        }
+        self.nmi_line = active;
+    }
+
+    /**
+     * Whether the cycle counter is odd, DMA transfers align to it.
+     */
+    pub fn is_odd_cycle(&self) -> bool {
+        self.cycles % 2 == 1
+    }
+
+    /**
+     * State a tracer sees before the instruction at the program counter
+     * executes, the instruction bytes are peeked from the bus.
+     */
+    pub fn trace_event(&self) -> TraceEvent {
+        let op_code = self.decode_instruction(self.memory.peek(self.program_counter));
+        let mut operands = [0; 2];
+        let operand_count = op_code.size.saturating_sub(1).min(2);
+        for (offset, operand) in operands.iter_mut().take(operand_count as usize).enumerate() {
+            *operand = self.memory.peek(self.program_counter.wrapping_add(1 + offset as u16));
+        }
+        TraceEvent {
+            program_counter: self.program_counter,
+            opcode: op_code.code,
+            operands,
+            operand_count,
+            register_a: self.register_a,
+            register_x: self.register_x,
+            register_y: self.register_y,
+            status: self.status,
+            stack_pointer: self.stack_pointer,
+            cycles: self.cycles
+        }
+    }
+
+    /**
+     * Halts the CPU for the given number of cycles before the next
+     * instruction, the next step reports them as Stalled.
+     */
+    pub fn stall(&mut self, cycles:u16) {
+        self.stall_cycles = self.stall_cycles.saturating_add(cycles);
+    }
+
+    /**
+     * Drives the IRQ input. The interrupt is serviced for as long as the line
+     * is active and the InterruptDisable flag is clear.
+     */
+    pub fn set_irq_line(&mut self, active: bool) {
+        self.irq_line = active;
     }
 
     /**
-     * Loads program into the memory
+     * Copies an image into memory at the address, wrapping around the end of
+     * the address space. Vectors and registers are left alone.
+     */
+    pub fn load_image(&mut self, addr:u16, image:&[u8]) {
+        for (offset, data) in image.iter().enumerate() {
+            self.memory.write(addr.wrapping_add(offset as u16), *data);
+        }
+    }
+
+    /**
+     * Copies every segment of the image into memory. The entry point is not
+     * applied, see set_reset_vector.
+     */
+    pub fn load(&mut self, image: &Image) {
+        for segment in &image.segments {
+            self.load_image(segment.address, &segment.data);
+        }
+    }
+
+    pub fn set_reset_vector(&mut self, addr:u16) {
+        self.memory.write_word(RESET_VECTOR, addr);
+    }
+
+    pub fn set_nmi_vector(&mut self, addr:u16) {
+        self.memory.write_word(NMI_VECTOR, addr);
+    }
+
+    pub fn set_irq_vector(&mut self, addr:u16) {
+        self.memory.write_word(IRQ_VECTOR, addr);
+    }
+
+    /**
+     * Loads program into the memory, bytes past $FFFF wrap around to $0000
      */
     pub fn load_program(&mut self, program:Vec<u8>) {
-        self.memory.mem_array[0x8000 .. (0x8000 + program.len())].copy_from_slice(&program[..]);
+        for (offset, data) in program.iter().enumerate() {
+            self.memory.write(0x8000u16.wrapping_add(offset as u16), *data);
+        }
         // set program counter
         self.program_counter = 0x8000;
         self.memory.write_word(0xFFFC, 0x8000)
     }
 
     /**
-     * Start execution of the program in the memory. 
+     * Start execution of the program in the memory.
      * Emulates fetch decode execute cycle.
+     * Execution stops after a BRK instruction or when the CPU cannot
+     * continue, use run_until to stop on other conditions.
      */
     pub fn run(&mut self) {
-        println!("Execution started.");
-        // Fetch Decode Execution cycle
+        self.run_until(StopCondition::Break);
+    }
+
+    /**
+     * Runs instructions until the stop condition is met or the CPU cannot continue.
+     */
+    pub fn run_until(&mut self, condition: StopCondition) -> StopReason {
+        let start_cycles = self.cycles;
+        let mut instructions:u64 = 0;
         loop {
-            if self.program_counter >= 0xFFFF {
-                break;
+            match condition {
+                StopCondition::ProgramCounter(addr) if self.program_counter == addr => return StopReason::ConditionMet,
+                StopCondition::Opcode(code) if self.memory.peek(self.program_counter) == code => return StopReason::ConditionMet,
+                StopCondition::Cycles(count) if self.cycles - start_cycles >= count => return StopReason::ConditionMet,
+                StopCondition::Instructions(count) if instructions >= count => return StopReason::ConditionMet,
+                _ => {}
+            }
+            let instruction_addr = self.program_counter;
+            match self.step() {
+                StepResult::Executed { .. } => {
+                    if condition == StopCondition::Trap && self.program_counter == instruction_addr {
+                        return StopReason::ConditionMet;
+                    }
+                },
+                // interrupt sequences and DMA halts are not instructions
+                StepResult::Interrupt { .. } | StepResult::Stalled { .. } => continue,
+                StepResult::Break { .. } => {
+                    if condition == StopCondition::Break {
+                        return StopReason::ConditionMet;
+                    }
+                },
+                StepResult::Jammed => return StopReason::Jammed,
+                StepResult::IllegalOpcode(code) => return StopReason::IllegalOpcode(code),
             }
-            // Fetch
-            let instruction = self.fetch_instruction();
-            self.program_counter += 1;
-            // Decode
-            let op_code = self.decode_instruction(instruction);
-            // Execute 
-            // TODO: Cycles from opcode
-            self.execute_instruction(instruction);
+            instructions += 1;
+        }
+    }
+
+    /**
+     * Executes a single instruction and reports what happened,
+     * the cycle count includes page crossing and branch penalties.
+     * Like the 6502, interrupt lines are polled at the end of each instruction,
+     * so an interrupt raised between two steps is serviced after the next instruction.
+     */
+    pub fn step(&mut self) -> StepResult {
+        if self.jammed {
+            return StepResult::Jammed;
+        }
+        // the bus can be taken over on the opcode fetch
+        self.halt(self.program_counter);
+        if self.halted_cycles > 0 {
+            let cycles = self.halted_cycles;
+            self.halted_cycles = 0;
+            self.sample_bus_nmi();
+            self.poll_interrupts(self.get_cpu_flag(CPUFlag::InterruptDisable));
+            return StepResult::Stalled { cycles };
+        }
+        if self.stall_cycles > 0 {
+            let cycles = self.stall_cycles;
+            self.stall_cycles = 0;
+            self.idle(cycles);
+            return StepResult::Stalled { cycles };
+        }
+        if self.nmi_pending || self.irq_pending {
+            return self.service_interrupt();
+        }
+        // Fetch
+        let instruction = self.fetch_instruction();
+        // Decode
+        let op_code = self.decode_instruction(instruction);
+        if op_code.is_unofficial() && !self.unofficial_opcodes {
+            self.diagnostic(Diagnostic::IllegalOpcode { program_counter: self.program_counter, opcode: instruction });
+            return StepResult::IllegalOpcode(instruction);
+        }
+        if op_code.instruction == Instruction::JAM {
+            // program counter is left on the opcode for inspection
+            self.jammed = true;
+            self.diagnostic(Diagnostic::Jammed { program_counter: self.program_counter, opcode: instruction });
+            return StepResult::Jammed;
+        }
+        if self.tracer.is_some() {
+            self.trace();
+        }
+        self.program_counter = self.program_counter.wrapping_add(1);
+        let status_before = self.status;
+        // Execute
+        self.page_crossed = false;
+        self.extra_cycles = 0;
+        self.execute_instruction(op_code);
+        // Count cycles
+        let mut cycles = op_code.cycles + self.extra_cycles;
+        if op_code.page_penalty && self.page_crossed {
+            cycles += 1;
+        }
+        self.cycles += cycles as u64;
+        self.tick(cycles);
+        self.sample_bus_nmi();
+        // CLI, SEI and PLP change the flag after the poll, the new value is seen one instruction later
+        let interrupt_disable = match op_code.instruction {
+            Instruction::CLI | Instruction::SEI | Instruction::PLP => status_before & CPUFlag::InterruptDisable.mask() != 0,
+            _ => self.get_cpu_flag(CPUFlag::InterruptDisable),
+        };
+        self.poll_interrupts(interrupt_disable);
+        match op_code.instruction {
+            Instruction::BRK => StepResult::Break { cycles },
+            _ => StepResult::Executed { cycles },
+        }
+    }
+
+    /**
+     * Runs the hardware interrupt sequence for the pending interrupt, NMI has priority over IRQ.
+     */
+    fn service_interrupt(&mut self) -> StepResult {
+        let kind = if self.nmi_pending { Interrupt::NMI } else { Interrupt::IRQ };
+        match kind {
+            Interrupt::NMI => {
+                self.nmi_pending = false;
+                self.interrupt_sequence(self.program_counter, NMI_VECTOR, false);
+            },
+            Interrupt::IRQ => {
+                self.irq_pending = false;
+                self.interrupt_sequence(self.program_counter, IRQ_VECTOR, false);
+            }
+        }
+        self.cycles += INTERRUPT_CYCLES as u64;
+        self.tick(INTERRUPT_CYCLES);
+        self.sample_bus_nmi();
+        self.poll_interrupts(true);
+        StepResult::Interrupt { kind, cycles: INTERRUPT_CYCLES }
+    }
+
+    /**
+     * Lets cycles pass without executing, devices on the bus keep running
+     * and interrupts raised meanwhile are serviced afterwards.
+     */
+    fn idle(&mut self, cycles:u16) {
+        for _ in 0..cycles {
+            self.cycles += 1;
+            self.memory.tick(1);
+            self.sample_bus_nmi();
+        }
+        self.poll_interrupts(self.get_cpu_flag(CPUFlag::InterruptDisable));
+    }
+
+    /**
+     * Reads a byte as a CPU bus cycle, devices are clocked up to the access.
+     */
+    fn read(&mut self, addr:u16) -> u8 {
+        self.halt(addr);
+        self.access_cycle();
+        self.memory.read(addr)
+    }
+
+    /**
+     * Lets the bus halt the CPU before a read, the halted cycles have run
+     * on the bus already and are reported as Stalled by the next step.
+     */
+    fn halt(&mut self, addr:u16) {
+        let cycles = self.memory.halt(addr);
+        self.cycles += cycles as u64;
+        self.halted_cycles = self.halted_cycles.saturating_add(cycles);
+    }
+
+    fn read_word(&mut self, addr:u16) -> u16 {
+        let lo = self.read(addr) as u16;
+        let hi = self.read(addr.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    /**
+     * Writes a byte as a CPU bus cycle, devices are clocked up to the access.
+     */
+    fn write(&mut self, addr:u16, data:u8) {
+        self.access_cycle();
+        self.memory.write(addr, data);
+    }
+
+    fn access_cycle(&mut self) {
+        self.memory.tick(1);
+        self.access_cycles = self.access_cycles.saturating_add(1);
+    }
+
+    /**
+     * Clocks the bus for the cycles of an instruction, interrupt or reset
+     * that were not bus accesses.
+     */
+    fn tick(&mut self, cycles:u8) {
+        let remaining = cycles.saturating_sub(self.access_cycles);
+        self.access_cycles = 0;
+        if remaining > 0 {
+            self.memory.tick(remaining);
+        }
+    }
+
+    /**
+     * Reports the instruction at the program counter to the tracer.
+     */
+    fn trace(&mut self) {
+        let event = self.trace_event();
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.instruction(&event);
+        }
+    }
+
+    fn diagnostic(&mut self, diagnostic: Diagnostic) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.diagnostic(&diagnostic);
         }
-        println!("Execution completed.");
+    }
+
+    /**
+     * Edge detects the NMI line driven by the bus.
+     */
+    fn sample_bus_nmi(&mut self) {
+        let active = self.memory.nmi();
+        if active && !self.bus_nmi_line {
+            self.nmi_detected = true;
+        }
+        self.bus_nmi_line = active;
+    }
+
+    /**
+     * Samples the interrupt inputs, the result is serviced before the next instruction.
+     */
+    fn poll_interrupts(&mut self, interrupt_disable: bool) {
+        if self.nmi_detected {
+            self.nmi_detected = false;
+            self.nmi_pending = true;
+        }
+        // devices on the bus share the IRQ line with the external input
+        self.irq_pending = (self.irq_line || self.memory.irq()) && !interrupt_disable;
     }
 
     /**
      * Fetch next instruction from memory using program counter.
      */
     fn fetch_instruction(&mut self) -> u8 {
-        self.memory.read_byte(self.program_counter)
+        self.read(self.program_counter)
     }
 
     /**
      * Decode instruction and get OpCode details.
      */
-    fn decode_instruction(&mut self, instruction: u8) -> &OpCode {
+    fn decode_instruction(&self, instruction: u8) -> &'static OpCode {
         self.op_codes.get_op_code(&instruction)
     }
-    
+
     /**
      * Execute the instruction logic
      */
-    fn execute_instruction(&mut self, instruction: u8) {
-        // decode
-        match instruction {
-            0x00 => self.op_nop(),
-            // LDA
-            0xA9 => self.op_lda(&AddressingMode::Immediate),
-            0xA5 => self.op_lda(&AddressingMode::ZeroPage),
-            0xB5 => self.op_lda(&AddressingMode::ZeroPage_X),
-            0xAD => self.op_lda(&AddressingMode::Absolute),
-            0xBD => self.op_lda(&AddressingMode::Absolute_X),
-            0xB9 => self.op_lda(&AddressingMode::Absolute_Y),
-            0xA1 => self.op_lda(&AddressingMode::Indirect_X),
-            0xB1 => self.op_lda(&AddressingMode::Indirect_Y),
-
-            // STA
-            0x85 => self.op_sta(&AddressingMode::ZeroPage),
-            0x95 => self.op_sta(&AddressingMode::ZeroPage_X),
-            0x8D => self.op_sta(&AddressingMode::Absolute),
-            0x9D => self.op_sta(&AddressingMode::Absolute_X),
-            0x99 => self.op_sta(&AddressingMode::Absolute_Y),
-            0x81 => self.op_sta(&AddressingMode::Indirect_X),
-            0x91 => self.op_sta(&AddressingMode::Indirect_Y),
-            
+    fn execute_instruction(&mut self, op_code: &OpCode) {
+        let mode = &op_code.mode;
+        match op_code.instruction {
+            Instruction::BRK => self.op_brk(),
+            Instruction::NOP => self.op_nop(mode),
+
+            // Arithmetic and logic
+            Instruction::ADC => self.op_adc(mode),
+            Instruction::SBC => self.op_sbc(mode),
+            Instruction::AND => self.op_and(mode),
+            Instruction::EOR => self.op_eor(mode),
+            Instruction::ORA => self.op_ora(mode),
+
+            // Shifts and rotates
+            Instruction::ASL => self.op_asl(mode),
+            Instruction::LSR => self.op_lsr(mode),
+            Instruction::ROL => self.op_rol(mode),
+            Instruction::ROR => self.op_ror(mode),
+
+            // Increments and decrements
+            Instruction::INC => self.op_inc(mode),
+            Instruction::DEC => self.op_dec(mode),
+            Instruction::INX => self.op_inx(),
+            Instruction::INY => self.op_iny(),
+            Instruction::DEX => self.op_dex(),
+            Instruction::DEY => self.op_dey(),
+
+            // Comparisons
+            Instruction::CMP => self.op_cmp(mode),
+            Instruction::CPX => self.op_cpx(mode),
+            Instruction::CPY => self.op_cpy(mode),
+            Instruction::BIT => self.op_bit(mode),
+
+            // Jumps and subroutines
+            Instruction::JMP => self.op_jmp(mode),
+            Instruction::JSR => self.op_jsr(mode),
+            Instruction::RTS => self.op_rts(),
+            Instruction::RTI => self.op_rti(),
+
+            // Branches
+            Instruction::BPL => self.op_bpl(mode),
+            Instruction::BMI => self.op_bmi(mode),
+            Instruction::BVC => self.op_bvc(mode),
+            Instruction::BVS => self.op_bvs(mode),
+            Instruction::BCC => self.op_bcc(mode),
+            Instruction::BCS => self.op_bcs(mode),
+            Instruction::BNE => self.op_bne(mode),
+            Instruction::BEQ => self.op_beq(mode),
+
+            // Loads and stores
+            Instruction::LDA => self.op_lda(mode),
+            Instruction::LDX => self.op_ldx(mode),
+            Instruction::LDY => self.op_ldy(mode),
+            Instruction::STA => self.op_sta(mode),
+            Instruction::STX => self.op_stx(mode),
+            Instruction::STY => self.op_sty(mode),
+
+            // Flag instructions
+            Instruction::CLC => self.op_clc(),
+            Instruction::SEC => self.op_sec(),
+            Instruction::CLI => self.op_cli(),
+            Instruction::SEI => self.op_sei(),
+            Instruction::CLV => self.op_clv(),
+            Instruction::CLD => self.op_cld(),
+            Instruction::SED => self.op_sed(),
+
             // Implicit instructions
-            0xE8 => self.op_inx(),
-            0xAA => self.op_tax(),
-            0x8A => self.op_txa(),
-            0xA8 => self.op_tay(),
-            0x98 => self.op_tya(),
-            0x9A => self.op_txs(),
-            0xBA => self.op_tsx(),
+            Instruction::TAX => self.op_tax(),
+            Instruction::TXA => self.op_txa(),
+            Instruction::TAY => self.op_tay(),
+            Instruction::TYA => self.op_tya(),
+            Instruction::TXS => self.op_txs(),
+            Instruction::TSX => self.op_tsx(),
 
             // Stack instructions
-            0x48 => self.op_pha(),
-            0x08 => self.op_php(),
-            0x68 => self.op_pla(),
-            0x28 => self.op_plp(),
-
-            _ => {
-                println!("Reaching address {} and no instruction exists: {}", self.program_counter, instruction);
-                self.op_nop();
-            }
+            Instruction::PHA => self.op_pha(),
+            Instruction::PHP => self.op_php(),
+            Instruction::PLA => self.op_pla(),
+            Instruction::PLP => self.op_plp(),
+
+            // Unofficial instructions
+            Instruction::ALR => self.op_alr(mode),
+            Instruction::ANC => self.op_anc(mode),
+            Instruction::ARR => self.op_arr(mode),
+            Instruction::AXS => self.op_axs(mode),
+            Instruction::DCP => self.op_dcp(mode),
+            Instruction::ISB => self.op_isb(mode),
+            Instruction::LAX => self.op_lax(mode),
+            Instruction::RLA => self.op_rla(mode),
+            Instruction::RRA => self.op_rra(mode),
+            Instruction::SAX => self.op_sax(mode),
+            Instruction::SLO => self.op_slo(mode),
+            Instruction::SRE => self.op_sre(mode),
+
+            // Unstable unofficial instructions
+            Instruction::AHX => self.op_ahx(mode),
+            Instruction::LAS => self.op_las(mode),
+            Instruction::LXA => self.op_lxa(mode),
+            Instruction::SHX => self.op_shx(mode),
+            Instruction::SHY => self.op_shy(mode),
+            Instruction::TAS => self.op_tas(mode),
+            Instruction::XAA => self.op_xaa(mode),
+
+            // Halts the CPU, handled by step
+            Instruction::JAM => {}
         }
     }
 
 }
 
 // Helper methods for CPU operations
-impl CPU {
+impl<B: Bus> CPU<B> {
 
     /**
      * Resolves the memory location of the operand by using the addressing mode.
@@ -166,63 +716,79 @@ impl CPU {
         match mode {
             AddressingMode::Immediate => {
                 let addr = self.program_counter;
-                self.program_counter += 1;
+                self.program_counter = self.program_counter.wrapping_add(1);
                 addr
             },
             AddressingMode::ZeroPage => {
-                let addr = self.memory.read_byte(self.program_counter) as u16;
-                self.program_counter += 1;
+                let addr = self.read(self.program_counter) as u16;
+                self.program_counter = self.program_counter.wrapping_add(1);
                 addr
             },
             AddressingMode::Absolute => {
-                let addr = self.memory.read_word(self.program_counter);
-                self.program_counter += 2;
+                let addr = self.read_word(self.program_counter);
+                self.program_counter = self.program_counter.wrapping_add(2);
                 addr
             },
             AddressingMode::ZeroPage_X => {
-                let pos = self.memory.read_byte(self.program_counter);
+                let pos = self.read(self.program_counter);
                 let addr = pos.wrapping_add(self.register_x) as u16;
-                self.program_counter += 1;
+                self.program_counter = self.program_counter.wrapping_add(1);
                 addr
             },
             AddressingMode::ZeroPage_Y => {
-                let pos = self.memory.read_byte(self.program_counter);
+                let pos = self.read(self.program_counter);
                 let addr = pos.wrapping_add(self.register_y) as u16;
-                self.program_counter += 1;
+                self.program_counter = self.program_counter.wrapping_add(1);
                 addr
             },
             AddressingMode::Absolute_X => {
-                let base = self.memory.read_word(self.program_counter);
+                let base = self.read_word(self.program_counter);
                 let addr = base.wrapping_add(self.register_x as u16);
-                self.program_counter += 2;
+                self.page_crossed = is_page_crossed(base, addr);
+                self.program_counter = self.program_counter.wrapping_add(2);
                 addr
             },
             AddressingMode::Absolute_Y => {
-                let base = self.memory.read_word(self.program_counter);
+                let base = self.read_word(self.program_counter);
                 let addr = base.wrapping_add(self.register_y as u16);
-                self.program_counter += 2;
+                self.page_crossed = is_page_crossed(base, addr);
+                self.program_counter = self.program_counter.wrapping_add(2);
                 addr
             },
+            AddressingMode::Relative => {
+                let offset = self.read(self.program_counter) as i8;
+                self.program_counter = self.program_counter.wrapping_add(1);
+                self.program_counter.wrapping_add(offset as u16)
+            },
+            AddressingMode::Indirect => {
+                let ptr = self.read_word(self.program_counter);
+                // 6502 bug: the pointer high byte is fetched without carrying into the page
+                let lo = self.read(ptr);
+                let hi = self.read((ptr & 0xFF00) | (ptr.wrapping_add(1) & 0x00FF));
+                self.program_counter = self.program_counter.wrapping_add(2);
+                (hi as u16) << 8 | (lo as u16)
+            },
             AddressingMode::Indirect_X => {
-                let base = self.memory.read_byte(self.program_counter);
-                let ptr: u8 = (base as u8).wrapping_add(self.register_x);
-                let lo = self.memory.read_byte(ptr as u16);
-                let hi = self.memory.read_byte(ptr.wrapping_add(1) as u16);
-                self.program_counter += 1;
+                let base = self.read(self.program_counter);
+                let ptr: u8 = base.wrapping_add(self.register_x);
+                let lo = self.read(ptr as u16);
+                let hi = self.read(ptr.wrapping_add(1) as u16);
+                self.program_counter = self.program_counter.wrapping_add(1);
                 (hi as u16) << 8 | (lo as u16)
             },
             AddressingMode::Indirect_Y => {
-                let base = self.memory.read_byte(self.program_counter);
-                let lo = self.memory.read_byte(base as u16);
-                let hi = self.memory.read_byte((base as u8).wrapping_add(1) as u16);
+                let base = self.read(self.program_counter);
+                let lo = self.read(base as u16);
+                let hi = self.read(base.wrapping_add(1) as u16);
                 let deref_base = (hi as u16) << 8 | (lo as u16);
                 let deref = deref_base.wrapping_add(self.register_y as u16);
-                self.program_counter += 1;
+                self.page_crossed = is_page_crossed(deref_base, deref);
+                self.program_counter = self.program_counter.wrapping_add(1);
                 deref
             },
-            AddressingMode::NoneAddressing => {
+            AddressingMode::NoneAddressing | AddressingMode::Accumulator => {
                 // No parameter is used for these kind of instructions, pass program counter.
-                return self.program_counter;
+                self.program_counter
             }
         }
     }
@@ -232,7 +798,7 @@ impl CPU {
      */
     pub fn resolve_stack_addr(&mut self, index: u8) -> u16 {
         let stack_base:u16 = 0x0100;
-        return stack_base + index as u16;
+        stack_base + index as u16
     }
 
     /**
@@ -240,7 +806,7 @@ impl CPU {
      */
     fn stack_push_byte(&mut self, data:u8) {
         let addr = self.resolve_stack_addr(self.stack_pointer);
-        self.memory.write_byte(addr, data);
+        self.write(addr, data);
         self.stack_pointer = self.stack_pointer.wrapping_sub(1)
     }
 
@@ -250,7 +816,24 @@ impl CPU {
     fn stack_pop_byte(&mut self) -> u8{
         self.stack_pointer = self.stack_pointer.wrapping_add(1);
         let addr = self.resolve_stack_addr(self.stack_pointer);
-        return self.memory.read_byte(addr);
+        self.read(addr)
+    }
+
+    /**
+     *  Pushes a word to the stack, high byte first
+     */
+    fn stack_push_word(&mut self, data:u16) {
+        self.stack_push_byte((data >> 8) as u8);
+        self.stack_push_byte((data & 0x00FF) as u8);
+    }
+
+    /**
+     *  Pops a word from the stack, low byte first
+     */
+    fn stack_pop_word(&mut self) -> u16 {
+        let lo = self.stack_pop_byte() as u16;
+        let hi = self.stack_pop_byte() as u16;
+        (hi << 8) | lo
     }
 
     /**
@@ -258,27 +841,25 @@ impl CPU {
      */
     pub fn set_cpu_flag(&mut self, flag: CPUFlag, val: bool) {
         // Map flag bits
-        let mask:u8 = match flag {
-            CPUFlag::Carry =>               0b0000_0001,
-            CPUFlag::Zero =>                0b0000_0010,
-            CPUFlag::InterruptDisable =>    0b0000_0100,
-            CPUFlag::Decimal =>             0b0000_1000,
-            CPUFlag::B0 =>                  0b0001_0000,
-            CPUFlag::B1 =>                  0b0010_0000,
-            CPUFlag::Overflow =>            0b0100_0000,
-            CPUFlag::Negative =>            0b1000_0000
-        };
+        let mask:u8 = flag.mask();
         // invert mask bits 00000001 -> 11111110
         let inverted_mask = 0b1111_1111 - mask;
-        // set cpu flag to zero 1010_1011 & 1111_1110 -> 1010_1010   
-        self.status = self.status & inverted_mask;
+        // set cpu flag to zero 1010_1011 & 1111_1110 -> 1010_1010
+        self.status &= inverted_mask;
         if val {
             // set cpu flag to 1
-            // 1010_1010 | 0000_0001 -> 1010_1010  
-            self.status = self.status | mask;
+            // 1010_1010 | 0000_0001 -> 1010_1010
+            self.status |= mask;
         }
     }
 
+    /**
+     *  Reads particular CPU flag
+     */
+    pub fn get_cpu_flag(&self, flag: CPUFlag) -> bool {
+        self.status & flag.mask() != 0
+    }
+
     /**
      * Update zero and negative flags regarding to the arithmetic result.
      */
@@ -288,25 +869,474 @@ impl CPU {
         self.set_cpu_flag(CPUFlag::Negative, result & 0b1000_0000 != 0);
     }
 
+    /**
+     * Decimal mode is only wired up on the NMOS 6502, the 2A03 ignores the D flag.
+     */
+    fn is_decimal_mode(&self) -> bool {
+        self.variant == CPUVariant::NMOS6502 && self.get_cpu_flag(CPUFlag::Decimal)
+    }
+
+    /**
+     * ADC: adds a value and the carry to the accumulator.
+     */
+    fn add_with_carry(&mut self, value: u8) {
+        if self.is_decimal_mode() {
+            self.add_decimal(value);
+        } else {
+            self.add_to_register_a(value);
+        }
+    }
+
+    /**
+     * SBC: subtracts a value and the inverted carry from the accumulator.
+     */
+    fn subtract_with_borrow(&mut self, value: u8) {
+        if self.is_decimal_mode() {
+            self.subtract_decimal(value);
+        } else {
+            // A - M - (1 - C) is the same as A + !M + C
+            self.add_to_register_a(!value);
+        }
+    }
+
+    /**
+     * Adds a value and the carry to the accumulator, updating C, V, Z and N.
+     * SBC is implemented as an addition of the inverted operand.
+     */
+    fn add_to_register_a(&mut self, value: u8) {
+        let carry = self.get_cpu_flag(CPUFlag::Carry) as u16;
+        let sum = self.register_a as u16 + value as u16 + carry;
+        let result = sum as u8;
+        // overflow when both operands have the same sign and the result sign differs
+        let overflow = (self.register_a ^ result) & (value ^ result) & 0b1000_0000 != 0;
+        self.set_cpu_flag(CPUFlag::Carry, sum > 0xFF);
+        self.set_cpu_flag(CPUFlag::Overflow, overflow);
+        self.register_a = result;
+        self.update_zn_flags(result);
+    }
+
+    /**
+     * BCD addition as done by the NMOS 6502. Z comes from the binary sum,
+     * N and V from the intermediate result before the high digit is adjusted.
+     */
+    fn add_decimal(&mut self, value: u8) {
+        let a = self.register_a as u16;
+        let operand = value as u16;
+        let carry = self.get_cpu_flag(CPUFlag::Carry) as u16;
+        let mut result = (a & 0x0F) + (operand & 0x0F) + carry;
+        if result > 0x09 {
+            result += 0x06;
+        }
+        result = if result <= 0x0F {
+            (result & 0x0F) + (a & 0xF0) + (operand & 0xF0)
+        } else {
+            (result & 0x0F) + (a & 0xF0) + (operand & 0xF0) + 0x10
+        };
+        self.set_cpu_flag(CPUFlag::Zero, (a + operand + carry) & 0xFF == 0);
+        self.set_cpu_flag(CPUFlag::Negative, result & 0x80 != 0);
+        self.set_cpu_flag(CPUFlag::Overflow, (a ^ result) & 0x80 != 0 && (a ^ operand) & 0x80 == 0);
+        if result & 0x1F0 > 0x90 {
+            result += 0x60;
+        }
+        self.set_cpu_flag(CPUFlag::Carry, result & 0xFF0 > 0xF0);
+        self.register_a = result as u8;
+    }
+
+    /**
+     * BCD subtraction as done by the NMOS 6502, all flags come from the binary result.
+     */
+    fn subtract_decimal(&mut self, value: u8) {
+        let a = self.register_a as i16;
+        let operand = value as i16;
+        let borrow = 1 - self.get_cpu_flag(CPUFlag::Carry) as i16;
+        let binary = a - operand - borrow;
+        let mut result = (a & 0x0F) - (operand & 0x0F) - borrow;
+        result = if result & 0x10 != 0 {
+            ((result - 0x06) & 0x0F) | ((a & 0xF0) - (operand & 0xF0) - 0x10)
+        } else {
+            (result & 0x0F) | ((a & 0xF0) - (operand & 0xF0))
+        };
+        if result & 0x100 != 0 {
+            result -= 0x60;
+        }
+        let binary_result = binary as u8;
+        self.set_cpu_flag(CPUFlag::Carry, binary >= 0);
+        self.set_cpu_flag(CPUFlag::Overflow, (a as u8 ^ binary_result) & (a as u8 ^ value) & 0x80 != 0);
+        self.update_zn_flags(binary_result);
+        self.register_a = result as u8;
+    }
+
+    /**
+     * Compares a register with a value, updating C, Z and N.
+     */
+    fn compare(&mut self, register: u8, value: u8) {
+        self.set_cpu_flag(CPUFlag::Carry, register >= value);
+        self.update_zn_flags(register.wrapping_sub(value));
+    }
+
+    /**
+     * Takes a relative branch if the condition holds, the offset is always consumed.
+     */
+    fn branch(&mut self, mode: & AddressingMode, condition: bool) {
+        let target = self.resolve_operand_addr(mode);
+        if condition {
+            // +1 cycle if the branch is taken, +2 if it lands on another page
+            self.extra_cycles += if is_page_crossed(self.program_counter, target) { 2 } else { 1 };
+            self.program_counter = target;
+        }
+    }
+
+    /**
+     * Reads a memory operand, applies a read-modify-write operation and stores the result back.
+     * In accumulator mode the operation is applied to register A instead.
+     */
+    fn modify_memory(&mut self, mode: & AddressingMode, operation: fn(&mut Self, u8) -> u8) -> u8 {
+        if *mode == AddressingMode::Accumulator {
+            self.register_a = operation(self, self.register_a);
+            return self.register_a;
+        }
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        // read-modify-write instructions write the unmodified value back first
+        self.write(addr, value);
+        let result = operation(self, value);
+        self.write(addr, result);
+        result
+    }
+
+    fn shift_left(&mut self, value: u8) -> u8 {
+        self.set_cpu_flag(CPUFlag::Carry, value & 0b1000_0000 != 0);
+        let result = value << 1;
+        self.update_zn_flags(result);
+        result
+    }
+
+    fn shift_right(&mut self, value: u8) -> u8 {
+        self.set_cpu_flag(CPUFlag::Carry, value & 0b0000_0001 != 0);
+        let result = value >> 1;
+        self.update_zn_flags(result);
+        result
+    }
+
+    fn rotate_left(&mut self, value: u8) -> u8 {
+        let carry = self.get_cpu_flag(CPUFlag::Carry) as u8;
+        self.set_cpu_flag(CPUFlag::Carry, value & 0b1000_0000 != 0);
+        let result = (value << 1) | carry;
+        self.update_zn_flags(result);
+        result
+    }
+
+    fn rotate_right(&mut self, value: u8) -> u8 {
+        let carry = self.get_cpu_flag(CPUFlag::Carry) as u8;
+        self.set_cpu_flag(CPUFlag::Carry, value & 0b0000_0001 != 0);
+        let result = (value >> 1) | (carry << 7);
+        self.update_zn_flags(result);
+        result
+    }
+
+    fn increment(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.update_zn_flags(result);
+        result
+    }
+
+    fn decrement(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.update_zn_flags(result);
+        result
+    }
+
+    /**
+     * Status register value as pushed by PHP and BRK, both B bits are set.
+     */
+    fn status_for_push(&self) -> u8 {
+        self.status | CPUFlag::B0.mask() | CPUFlag::B1.mask()
+    }
+
+    /**
+     * Pushes the return address and status, then jumps through the vector.
+     * An NMI edge during the pushes, before the vector is fetched, hijacks
+     * the sequence, which makes a BRK or IRQ continue at the NMI handler.
+     * An NMI detected before the sequence started is serviced after it.
+     */
+    fn interrupt_sequence(&mut self, return_addr: u16, vector: u16, brk: bool) {
+        let nmi_latched = self.nmi_detected;
+        self.nmi_detected = false;
+        self.stack_push_word(return_addr);
+        let status = if brk {
+            self.status_for_push()
+        } else {
+            (self.status & !CPUFlag::B0.mask()) | CPUFlag::B1.mask()
+        };
+        self.stack_push_byte(status);
+        self.set_cpu_flag(CPUFlag::InterruptDisable, true);
+        self.sample_bus_nmi();
+        let vector = if self.nmi_detected {
+            self.nmi_detected = false;
+            NMI_VECTOR
+        } else {
+            vector
+        };
+        self.nmi_detected = nmi_latched;
+        self.program_counter = self.read_word(vector);
+    }
+
+    /**
+     * Restores the status register from the stack. The B flag does not exist
+     * in the register so it is dropped, the unused bit always reads as 1.
+     */
+    fn restore_status(&mut self, value: u8) {
+        self.status = (value & !CPUFlag::B0.mask()) | CPUFlag::B1.mask();
+    }
+
+}
+
+/**
+ * Checks whether two addresses are on different memory pages.
+ */
+fn is_page_crossed(addr_a: u16, addr_b: u16) -> bool {
+    addr_a & 0xFF00 != addr_b & 0xFF00
 }
 
 // OpCodes implementations
-impl CPU {
-    fn op_nop(&mut self) {}
+impl<B: Bus> CPU<B> {
+    fn op_nop(&mut self, mode: & AddressingMode) {
+        // Unofficial NOPs with an operand still read it
+        if *mode != AddressingMode::NoneAddressing {
+            let addr = self.resolve_operand_addr(mode);
+            self.read(addr);
+        }
+    }
+
+    fn op_brk(&mut self) {
+        // BRK has a padding byte, return address skips it
+        let return_addr = self.program_counter.wrapping_add(1);
+        self.interrupt_sequence(return_addr, IRQ_VECTOR, true);
+    }
 
     fn op_lda(&mut self, mode: & AddressingMode) {
         let addr = self.resolve_operand_addr(mode);
-        let value = self.memory.read_byte(addr);
+        let value = self.read(addr);
         // Update register
         self.register_a = value;
         // Update flags
         self.update_zn_flags(value);
-        println!("lda")
+    }
+
+    fn op_ldx(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.register_x = value;
+        self.update_zn_flags(value);
+    }
+
+    fn op_ldy(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.register_y = value;
+        self.update_zn_flags(value);
     }
 
     fn op_sta(&mut self, mode: & AddressingMode) {
         let addr = self.resolve_operand_addr(mode);
-        self.memory.write_byte(addr, self.register_a);
+        self.write(addr, self.register_a);
+    }
+
+    fn op_stx(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        self.write(addr, self.register_x);
+    }
+
+    fn op_sty(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        self.write(addr, self.register_y);
+    }
+
+    fn op_adc(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.add_with_carry(value);
+    }
+
+    fn op_sbc(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.subtract_with_borrow(value);
+    }
+
+    fn op_and(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.register_a &= value;
+        self.update_zn_flags(self.register_a);
+    }
+
+    fn op_eor(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.register_a ^= value;
+        self.update_zn_flags(self.register_a);
+    }
+
+    fn op_ora(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.register_a |= value;
+        self.update_zn_flags(self.register_a);
+    }
+
+    fn op_asl(&mut self, mode: & AddressingMode) {
+        self.modify_memory(mode, Self::shift_left);
+    }
+
+    fn op_lsr(&mut self, mode: & AddressingMode) {
+        self.modify_memory(mode, Self::shift_right);
+    }
+
+    fn op_rol(&mut self, mode: & AddressingMode) {
+        self.modify_memory(mode, Self::rotate_left);
+    }
+
+    fn op_ror(&mut self, mode: & AddressingMode) {
+        self.modify_memory(mode, Self::rotate_right);
+    }
+
+    fn op_inc(&mut self, mode: & AddressingMode) {
+        self.modify_memory(mode, Self::increment);
+    }
+
+    fn op_dec(&mut self, mode: & AddressingMode) {
+        self.modify_memory(mode, Self::decrement);
+    }
+
+    fn op_inx(&mut self) {
+        // increment register x
+        self.register_x = self.register_x.wrapping_add(1);
+        self.update_zn_flags(self.register_x);
+    }
+
+    fn op_iny(&mut self) {
+        self.register_y = self.register_y.wrapping_add(1);
+        self.update_zn_flags(self.register_y);
+    }
+
+    fn op_dex(&mut self) {
+        self.register_x = self.register_x.wrapping_sub(1);
+        self.update_zn_flags(self.register_x);
+    }
+
+    fn op_dey(&mut self) {
+        self.register_y = self.register_y.wrapping_sub(1);
+        self.update_zn_flags(self.register_y);
+    }
+
+    fn op_cmp(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.compare(self.register_a, value);
+    }
+
+    fn op_cpx(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.compare(self.register_x, value);
+    }
+
+    fn op_cpy(&mut self, mode: & AddressingMode) {
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.compare(self.register_y, value);
+    }
+
+    fn op_bit(&mut self, mode: & AddressingMode) {
+        // Z from A & M, N and V are copied from bits 7 and 6 of the operand
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.set_cpu_flag(CPUFlag::Zero, self.register_a & value == 0);
+        self.set_cpu_flag(CPUFlag::Negative, value & 0b1000_0000 != 0);
+        self.set_cpu_flag(CPUFlag::Overflow, value & 0b0100_0000 != 0);
+    }
+
+    fn op_jmp(&mut self, mode: & AddressingMode) {
+        self.program_counter = self.resolve_operand_addr(mode);
+    }
+
+    fn op_jsr(&mut self, mode: & AddressingMode) {
+        // Pushes the address of the last byte of the instruction
+        let target = self.resolve_operand_addr(mode);
+        let return_addr = self.program_counter.wrapping_sub(1);
+        self.stack_push_word(return_addr);
+        self.program_counter = target;
+    }
+
+    fn op_rts(&mut self) {
+        self.program_counter = self.stack_pop_word().wrapping_add(1);
+    }
+
+    fn op_rti(&mut self) {
+        let status = self.stack_pop_byte();
+        self.restore_status(status);
+        self.program_counter = self.stack_pop_word();
+    }
+
+    fn op_bpl(&mut self, mode: & AddressingMode) {
+        self.branch(mode, !self.get_cpu_flag(CPUFlag::Negative));
+    }
+
+    fn op_bmi(&mut self, mode: & AddressingMode) {
+        self.branch(mode, self.get_cpu_flag(CPUFlag::Negative));
+    }
+
+    fn op_bvc(&mut self, mode: & AddressingMode) {
+        self.branch(mode, !self.get_cpu_flag(CPUFlag::Overflow));
+    }
+
+    fn op_bvs(&mut self, mode: & AddressingMode) {
+        self.branch(mode, self.get_cpu_flag(CPUFlag::Overflow));
+    }
+
+    fn op_bcc(&mut self, mode: & AddressingMode) {
+        self.branch(mode, !self.get_cpu_flag(CPUFlag::Carry));
+    }
+
+    fn op_bcs(&mut self, mode: & AddressingMode) {
+        self.branch(mode, self.get_cpu_flag(CPUFlag::Carry));
+    }
+
+    fn op_bne(&mut self, mode: & AddressingMode) {
+        self.branch(mode, !self.get_cpu_flag(CPUFlag::Zero));
+    }
+
+    fn op_beq(&mut self, mode: & AddressingMode) {
+        self.branch(mode, self.get_cpu_flag(CPUFlag::Zero));
+    }
+
+    fn op_clc(&mut self) {
+        self.set_cpu_flag(CPUFlag::Carry, false);
+    }
+
+    fn op_sec(&mut self) {
+        self.set_cpu_flag(CPUFlag::Carry, true);
+    }
+
+    fn op_cli(&mut self) {
+        self.set_cpu_flag(CPUFlag::InterruptDisable, false);
+    }
+
+    fn op_sei(&mut self) {
+        self.set_cpu_flag(CPUFlag::InterruptDisable, true);
+    }
+
+    fn op_clv(&mut self) {
+        self.set_cpu_flag(CPUFlag::Overflow, false);
+    }
+
+    fn op_cld(&mut self) {
+        self.set_cpu_flag(CPUFlag::Decimal, false);
+    }
+
+    fn op_sed(&mut self) {
+        self.set_cpu_flag(CPUFlag::Decimal, true);
     }
 
     fn op_tax(&mut self) {
@@ -314,14 +1344,6 @@ impl CPU {
         let val_a = self.register_a;
         self.register_x = val_a;
         self.update_zn_flags(val_a);
-        println!("tax")
-    }
-
-    fn op_inx(&mut self) {
-        // increment register a
-        self.register_x += 1;
-        self.update_zn_flags(self.register_x);
-        println!("inx")
     }
 
     fn op_txs(&mut self) {
@@ -330,18 +1352,15 @@ impl CPU {
     }
 
     fn op_tsx(&mut self) {
-        // Copies the current contents of the stack register 
+        // Copies the current contents of the stack register
         // into the X register and sets the zero and negative flags as appropriate.
         self.register_x = self.stack_pointer;
         self.update_zn_flags(self.register_x);
     }
 
     fn op_pha(&mut self) {
-        // push a to stack
+        // push a to stack, flags are not affected
         self.stack_push_byte(self.register_a);
-        // set flags
-        self.update_zn_flags(self.register_a);
-        println!("pha");
     }
 
     fn op_pla(&mut self) {
@@ -350,21 +1369,18 @@ impl CPU {
         self.register_a = value;
         // set flags
         self.update_zn_flags(self.register_a);
-        println!("pla");
     }
-    
+
     fn op_php(&mut self) {
-        // Pushes a copy of the status flags on to the stack.
-        let status_flag = self.status;
+        // Pushes a copy of the status flags on to the stack, with the B flag set.
+        let status_flag = self.status_for_push();
         self.stack_push_byte(status_flag);
-        println!("php");
     }
 
     fn op_plp(&mut self) {
         // Pulls an 8 bit value from the stack and into the processor flags.
         let status = self.stack_pop_byte();
-        self.status = status;
-        println!("plp");
+        self.restore_status(status);
     }
 
     fn op_txa(&mut self) {
@@ -387,3 +1403,186 @@ impl CPU {
 
 }
 
+// Unofficial opcodes implementations
+impl<B: Bus> CPU<B> {
+    fn op_lax(&mut self, mode: & AddressingMode) {
+        // LDA and LDX with the same operand
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.register_a = value;
+        self.register_x = value;
+        self.update_zn_flags(value);
+    }
+
+    fn op_sax(&mut self, mode: & AddressingMode) {
+        // Stores A & X, flags are not affected
+        let addr = self.resolve_operand_addr(mode);
+        self.write(addr, self.register_a & self.register_x);
+    }
+
+    fn op_dcp(&mut self, mode: & AddressingMode) {
+        // DEC then CMP
+        let value = self.modify_memory(mode, Self::decrement);
+        self.compare(self.register_a, value);
+    }
+
+    fn op_isb(&mut self, mode: & AddressingMode) {
+        // INC then SBC
+        let value = self.modify_memory(mode, Self::increment);
+        self.subtract_with_borrow(value);
+    }
+
+    fn op_slo(&mut self, mode: & AddressingMode) {
+        // ASL then ORA
+        let value = self.modify_memory(mode, Self::shift_left);
+        self.register_a |= value;
+        self.update_zn_flags(self.register_a);
+    }
+
+    fn op_rla(&mut self, mode: & AddressingMode) {
+        // ROL then AND
+        let value = self.modify_memory(mode, Self::rotate_left);
+        self.register_a &= value;
+        self.update_zn_flags(self.register_a);
+    }
+
+    fn op_sre(&mut self, mode: & AddressingMode) {
+        // LSR then EOR
+        let value = self.modify_memory(mode, Self::shift_right);
+        self.register_a ^= value;
+        self.update_zn_flags(self.register_a);
+    }
+
+    fn op_rra(&mut self, mode: & AddressingMode) {
+        // ROR then ADC, the carry out of the rotate feeds the addition
+        let value = self.modify_memory(mode, Self::rotate_right);
+        self.add_with_carry(value);
+    }
+
+    fn op_anc(&mut self, mode: & AddressingMode) {
+        // AND, then bit 7 is copied to the carry
+        let addr = self.resolve_operand_addr(mode);
+        self.register_a &= self.read(addr);
+        self.update_zn_flags(self.register_a);
+        self.set_cpu_flag(CPUFlag::Carry, self.register_a & 0b1000_0000 != 0);
+    }
+
+    fn op_alr(&mut self, mode: & AddressingMode) {
+        // AND then LSR A
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.register_a & self.read(addr);
+        self.register_a = self.shift_right(value);
+    }
+
+    fn op_arr(&mut self, mode: & AddressingMode) {
+        // AND then ROR A, with C and V taken from bits 6 and 5 of the result
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.register_a & self.read(addr);
+        let carry = self.get_cpu_flag(CPUFlag::Carry) as u8;
+        let mut result = (value >> 1) | (carry << 7);
+        if self.is_decimal_mode() {
+            // flags come from the binary result, then both digits are BCD fixed
+            self.set_cpu_flag(CPUFlag::Negative, carry != 0);
+            self.set_cpu_flag(CPUFlag::Zero, result == 0);
+            self.set_cpu_flag(CPUFlag::Overflow, (value ^ result) & 0b0100_0000 != 0);
+            let low = value & 0x0F;
+            let high = value >> 4;
+            if low + (low & 0x01) > 0x05 {
+                result = (result & 0xF0) | (result.wrapping_add(0x06) & 0x0F);
+            }
+            let carry_out = high + (high & 0x01) > 0x05;
+            if carry_out {
+                result = result.wrapping_add(0x60);
+            }
+            self.set_cpu_flag(CPUFlag::Carry, carry_out);
+        } else {
+            self.update_zn_flags(result);
+            self.set_cpu_flag(CPUFlag::Carry, result & 0b0100_0000 != 0);
+            self.set_cpu_flag(CPUFlag::Overflow, ((result >> 6) ^ (result >> 5)) & 0x01 != 0);
+        }
+        self.register_a = result;
+    }
+
+    fn op_axs(&mut self, mode: & AddressingMode) {
+        // X = (A & X) - M, carry and flags as in CMP, decimal mode is ignored
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        let a_and_x = self.register_a & self.register_x;
+        self.set_cpu_flag(CPUFlag::Carry, a_and_x >= value);
+        self.register_x = a_and_x.wrapping_sub(value);
+        self.update_zn_flags(self.register_x);
+    }
+
+    fn op_las(&mut self, mode: & AddressingMode) {
+        // A, X and S are all set to M & S
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr) & self.stack_pointer;
+        self.register_a = value;
+        self.register_x = value;
+        self.stack_pointer = value;
+        self.update_zn_flags(value);
+    }
+
+    fn op_xaa(&mut self, mode: & AddressingMode) {
+        // A = (A | magic) & X & M
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.register_a = (self.register_a | UNSTABLE_MAGIC) & self.register_x & value;
+        self.update_zn_flags(self.register_a);
+    }
+
+    fn op_lxa(&mut self, mode: & AddressingMode) {
+        // A = X = (A | magic) & M
+        let addr = self.resolve_operand_addr(mode);
+        let value = self.read(addr);
+        self.register_a = (self.register_a | UNSTABLE_MAGIC) & value;
+        self.register_x = self.register_a;
+        self.update_zn_flags(self.register_a);
+    }
+
+    fn op_ahx(&mut self, mode: & AddressingMode) {
+        self.store_and_high_byte(mode, self.register_a & self.register_x);
+    }
+
+    fn op_shx(&mut self, mode: & AddressingMode) {
+        self.store_and_high_byte(mode, self.register_x);
+    }
+
+    fn op_shy(&mut self, mode: & AddressingMode) {
+        self.store_and_high_byte(mode, self.register_y);
+    }
+
+    fn op_tas(&mut self, mode: & AddressingMode) {
+        // S = A & X, then stored like AHX
+        self.stack_pointer = self.register_a & self.register_x;
+        self.store_and_high_byte(mode, self.stack_pointer);
+    }
+
+    /**
+     * Shared store of AHX, SHX, SHY and TAS. The value is ANDed with the high byte
+     * of the base address plus one. When indexing crosses a page the stored value
+     * also replaces the high byte of the target address.
+     */
+    fn store_and_high_byte(&mut self, mode: & AddressingMode, value: u8) {
+        let addr = self.resolve_operand_addr(mode);
+        let index = match mode {
+            AddressingMode::Absolute_X => self.register_x,
+            _ => self.register_y,
+        };
+        let base = addr.wrapping_sub(index as u16);
+        let result = value & ((base >> 8) as u8).wrapping_add(1);
+        let addr = if is_page_crossed(base, addr) {
+            ((result as u16) << 8) | (addr & 0x00FF)
+        } else {
+            addr
+        };
+        self.write(addr, result);
+    }
+
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
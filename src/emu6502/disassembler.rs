@@ -0,0 +1,164 @@
+use std::fmt;
+
+use crate::emu6502::bus::Bus;
+use crate::emu6502::op_codes::{AddressingMode, Instruction, OP_CODES_TABLE};
+
+/**
+ * One decoded instruction.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    pub address:u16,
+    /* Opcode and operand bytes, only size of them are valid */
+    pub bytes:[u8; 3],
+    pub size:u8,
+    /* Mnemonic without the unofficial marker, ".byte" for data */
+    pub mnemonic:&'static str,
+    pub operand:String,
+    pub unofficial:bool,
+    /* Branch, JMP and JSR destination */
+    pub target:Option<u16>
+}
+
+impl Disassembly {
+    /**
+     * Valid instruction bytes.
+     */
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.size as usize]
+    }
+
+    /**
+     * Address of the instruction that follows.
+     */
+    pub fn next_address(&self) -> u16 {
+        self.address.wrapping_add(self.size as u16)
+    }
+
+    /**
+     * Formats as a listing line, "C000  A9 10     LDA #$10".
+     */
+    pub fn listing(&self) -> String {
+        let bytes = self.bytes().iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ");
+        format!("{:04X}  {:<8}  {}", self.address, bytes, self)
+    }
+}
+
+impl fmt::Display for Disassembly {
+    /**
+     * Standard assembler syntax, unofficial opcodes are prefixed with '*'.
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.unofficial {
+            write!(f, "*")?;
+        }
+        if self.operand.is_empty() {
+            write!(f, "{}", self.mnemonic)
+        } else {
+            write!(f, "{} {}", self.mnemonic, self.operand)
+        }
+    }
+}
+
+/**
+ * Decodes the instruction at pc without side effects on the bus.
+ */
+pub fn disassemble_one<B: Bus + ?Sized>(bus: &B, pc:u16) -> Disassembly {
+    decode(pc, |addr| Some(bus.peek(addr)))
+}
+
+/**
+ * Decodes instructions starting at start until one begins past end. Stops
+ * at the end of the address space instead of wrapping.
+ */
+pub fn disassemble<B: Bus + ?Sized>(bus: &B, start:u16, end:u16) -> Vec<Disassembly> {
+    let mut lines = Vec::new();
+    let mut pc = start;
+    while pc <= end {
+        let line = disassemble_one(bus, pc);
+        let next = line.next_address();
+        lines.push(line);
+        if next <= pc {
+            break;
+        }
+        pc = next;
+    }
+    lines
+}
+
+/**
+ * Decodes a block of code that is mapped at origin, a ROM bank for example.
+ * An instruction cut off by the end of the block is listed as data.
+ */
+pub fn disassemble_bytes(data:&[u8], origin:u16) -> Vec<Disassembly> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let line = decode(origin.wrapping_add(offset as u16), |addr| {
+            data.get(addr.wrapping_sub(origin) as usize).copied()
+        });
+        offset += line.size as usize;
+        lines.push(line);
+    }
+    lines
+}
+
+/**
+ * Decodes the instruction at address, fetch returns None for bytes that
+ * are not available.
+ */
+pub(crate) fn decode<F: Fn(u16) -> Option<u8>>(address:u16, fetch: F) -> Disassembly {
+    let opcode = fetch(address).unwrap_or(0);
+    let op_code = &OP_CODES_TABLE[opcode as usize];
+    let mut bytes = [opcode, 0, 0];
+    for offset in 1..op_code.size {
+        match fetch(address.wrapping_add(offset as u16)) {
+            Some(byte) => bytes[offset as usize] = byte,
+            None => return Disassembly {
+                address,
+                bytes,
+                size: 1,
+                mnemonic: ".byte",
+                operand: format!("${:02X}", opcode),
+                unofficial: false,
+                target: None
+            }
+        }
+    }
+    let byte = bytes[1];
+    let word = u16::from_le_bytes([bytes[1], bytes[2]]);
+    let mut target = None;
+    let operand = match op_code.mode {
+        AddressingMode::NoneAddressing => String::new(),
+        AddressingMode::Accumulator => String::from("A"),
+        AddressingMode::Immediate => format!("#${:02X}", byte),
+        AddressingMode::Relative => {
+            let destination = address.wrapping_add(2).wrapping_add(byte as i8 as u16);
+            target = Some(destination);
+            format!("${:04X}", destination)
+        },
+        AddressingMode::ZeroPage => format!("${:02X}", byte),
+        AddressingMode::ZeroPage_X => format!("${:02X},X", byte),
+        AddressingMode::ZeroPage_Y => format!("${:02X},Y", byte),
+        AddressingMode::Absolute => {
+            if let Instruction::JMP | Instruction::JSR = op_code.instruction {
+                target = Some(word);
+            }
+            format!("${:04X}", word)
+        },
+        AddressingMode::Absolute_X => format!("${:04X},X", word),
+        AddressingMode::Absolute_Y => format!("${:04X},Y", word),
+        AddressingMode::Indirect => format!("(${:04X})", word),
+        AddressingMode::Indirect_X => format!("(${:02X},X)", byte),
+        AddressingMode::Indirect_Y => format!("(${:02X}),Y", byte)
+    };
+    Disassembly {
+        address,
+        bytes,
+        size: op_code.size,
+        mnemonic: op_code.name.trim_start_matches('*'),
+        operand,
+        unofficial: op_code.is_unofficial(),
+        target
+    }
+}
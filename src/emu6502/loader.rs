@@ -0,0 +1,255 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/**
+ * File formats a program image can be read from.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /* Plain bytes placed at the given address */
+    Raw(u16),
+    IntelHex,
+    /* Motorola S-record, S19/S28/S37 */
+    SRecord,
+    /* Commodore program file, a little endian load address followed by the data */
+    Prg
+}
+
+impl ImageFormat {
+    /**
+     * Guesses the format from the file extension, None for raw binaries
+     * which need a load address.
+     */
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<ImageFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "hex" | "ihx" | "ihex" => Some(ImageFormat::IntelHex),
+            "s19" | "s28" | "s37" | "srec" | "mot" => Some(ImageFormat::SRecord),
+            "prg" => Some(ImageFormat::Prg),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoaderError {
+    /* File could not be read */
+    Io(std::io::Error),
+    /* Line is not a well formed record */
+    InvalidRecord { line:usize },
+    ChecksumMismatch { line:usize },
+    UnsupportedRecord { line:usize, kind:u8 },
+    /* Data does not fit in the 64 KiB address space */
+    AddressOutOfRange { line:usize, address:u32 },
+    /* PRG file without the 2 byte load address */
+    MissingLoadAddress
+}
+
+impl fmt::Display for LoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoaderError::Io(err) => write!(f, "cannot read image file: {}", err),
+            LoaderError::InvalidRecord { line } => write!(f, "line {}: invalid record", line),
+            LoaderError::ChecksumMismatch { line } => write!(f, "line {}: checksum mismatch", line),
+            LoaderError::UnsupportedRecord { line, kind } => write!(f, "line {}: unsupported record type {}", line, kind),
+            LoaderError::AddressOutOfRange { line, address } =>
+                write!(f, "line {}: address ${:X} is outside the 64 KiB address space", line, address),
+            LoaderError::MissingLoadAddress => write!(f, "file is too short for a load address")
+        }
+    }
+}
+
+impl std::error::Error for LoaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoaderError::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<std::io::Error> for LoaderError {
+    fn from(err: std::io::Error) -> Self {
+        LoaderError::Io(err)
+    }
+}
+
+/**
+ * Contiguous block of bytes to place at an address.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub address:u16,
+    pub data:Vec<u8>
+}
+
+/**
+ * Program image made of segments, with the entry point when the file gives one.
+ * Load it with CPU::load.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Image {
+    pub segments:Vec<Segment>,
+    pub entry_point:Option<u16>
+}
+
+impl Image {
+    /**
+     * Reads an image file in the given format.
+     */
+    pub fn load<P: AsRef<Path>>(path: P, format: ImageFormat) -> Result<Image, LoaderError> {
+        let data = fs::read(path)?;
+        match format {
+            ImageFormat::Raw(address) => Ok(Image::raw(address, &data)),
+            ImageFormat::Prg => Image::from_prg(&data),
+            ImageFormat::IntelHex => Image::from_intel_hex(&String::from_utf8_lossy(&data)),
+            ImageFormat::SRecord => Image::from_srecord(&String::from_utf8_lossy(&data))
+        }
+    }
+
+    /**
+     * Image of plain bytes placed at the address.
+     */
+    pub fn raw(address:u16, data:&[u8]) -> Image {
+        Image { segments: vec![Segment { address, data: data.to_vec() }], entry_point: None }
+    }
+
+    /**
+     * Parses a C64 style .prg file.
+     */
+    pub fn from_prg(data:&[u8]) -> Result<Image, LoaderError> {
+        if data.len() < 2 {
+            return Err(LoaderError::MissingLoadAddress);
+        }
+        let address = u16::from_le_bytes([data[0], data[1]]);
+        Ok(Image::raw(address, &data[2..]))
+    }
+
+    /**
+     * Parses Intel HEX. Extended segment and linear address records are
+     * accepted as long as the data stays below 64 KiB.
+     */
+    pub fn from_intel_hex(text:&str) -> Result<Image, LoaderError> {
+        let mut image = Image::default();
+        let mut base:u32 = 0;
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let bytes = line.strip_prefix(':')
+                .and_then(parse_hex_bytes)
+                .ok_or(LoaderError::InvalidRecord { line: line_number })?;
+            if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+                return Err(LoaderError::InvalidRecord { line: line_number });
+            }
+            if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+                return Err(LoaderError::ChecksumMismatch { line: line_number });
+            }
+            let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+            let data = &bytes[4..bytes.len() - 1];
+            let expected_length = match bytes[3] {
+                0x02 | 0x04 => Some(2),
+                0x03 | 0x05 => Some(4),
+                _ => None
+            };
+            if expected_length.is_some_and(|length| data.len() != length) {
+                return Err(LoaderError::InvalidRecord { line: line_number });
+            }
+            match bytes[3] {
+                0x00 => image.add_data(base + offset, data, line_number)?,
+                0x01 => break,
+                0x02 => base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 4,
+                0x04 => base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 16,
+                0x03 => {
+                    let segment = u16::from_be_bytes([data[0], data[1]]) as u32;
+                    let pointer = u16::from_be_bytes([data[2], data[3]]) as u32;
+                    image.entry_point = Some(entry_point((segment << 4) + pointer, line_number)?);
+                },
+                0x05 => image.entry_point = Some(entry_point(u32::from_be_bytes([data[0], data[1], data[2], data[3]]), line_number)?),
+                kind => return Err(LoaderError::UnsupportedRecord { line: line_number, kind })
+            }
+        }
+        Ok(image)
+    }
+
+    /**
+     * Parses Motorola S-records. S1-S3 carry data, S7-S9 the entry point,
+     * header and count records are skipped.
+     */
+    pub fn from_srecord(text:&str) -> Result<Image, LoaderError> {
+        let mut image = Image::default();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = LoaderError::InvalidRecord { line: line_number };
+            let kind = match line.as_bytes() {
+                [b'S', kind @ b'0'..=b'9', ..] => kind - b'0',
+                _ => return Err(invalid)
+            };
+            let bytes = parse_hex_bytes(&line[2..]).ok_or(invalid)?;
+            if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+                return Err(LoaderError::InvalidRecord { line: line_number });
+            }
+            if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0xFF {
+                return Err(LoaderError::ChecksumMismatch { line: line_number });
+            }
+            let address_size = match kind {
+                0 | 1 | 5 | 9 => 2,
+                2 | 6 | 8 => 3,
+                3 | 7 => 4,
+                _ => return Err(LoaderError::UnsupportedRecord { line: line_number, kind })
+            };
+            if bytes.len() < address_size + 2 {
+                return Err(LoaderError::InvalidRecord { line: line_number });
+            }
+            let address = bytes[1..=address_size].iter().fold(0u32, |address, byte| (address << 8) | *byte as u32);
+            let data = &bytes[address_size + 1..bytes.len() - 1];
+            match kind {
+                1..=3 => image.add_data(address, data, line_number)?,
+                7..=9 => image.entry_point = Some(entry_point(address, line_number)?),
+                _ => {}
+            }
+        }
+        Ok(image)
+    }
+
+    /**
+     * Appends data, extending the last segment when it continues it.
+     */
+    fn add_data(&mut self, address:u32, data:&[u8], line:usize) -> Result<(), LoaderError> {
+        if address.checked_add(data.len() as u32).is_none_or(|end| end > 0x10000) {
+            return Err(LoaderError::AddressOutOfRange { line, address });
+        }
+        match self.segments.last_mut() {
+            Some(segment) if segment.address as u32 + segment.data.len() as u32 == address => {
+                segment.data.extend_from_slice(data);
+            },
+            _ => self.segments.push(Segment { address: address as u16, data: data.to_vec() })
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Start address of a record, it has to fit in the 64 KiB address space.
+ */
+fn entry_point(address:u32, line:usize) -> Result<u16, LoaderError> {
+    u16::try_from(address).map_err(|_| LoaderError::AddressOutOfRange { line, address })
+}
+
+/**
+ * Decodes a string of hex digit pairs.
+ */
+fn parse_hex_bytes(text:&str) -> Option<Vec<u8>> {
+    // from_str_radix alone would accept a sign
+    if text.len() & 0x01 != 0 || !text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|index| u8::from_str_radix(&text[index..index + 2], 16).ok()).collect()
+}
@@ -1,11 +1,13 @@
+use crate::emu6502::bus::Bus;
+
 pub struct RAM {
-    pub mem_array: [u8; 0xFFFF]
+    pub mem_array: [u8; 0x10000]
 }
 
 impl RAM {
     pub fn new() -> Self {
         RAM { 
-            mem_array: [0; 0xFFFF]
+            mem_array: [0; 0x10000]
         }
     }
 
@@ -17,20 +19,43 @@ impl RAM {
 
     pub fn read_byte(&mut self, index:u16) -> u8 {
         // load from memory
-        return self.mem_array[index as usize];
+        self.mem_array[index as usize]
     }
 
     pub fn write_word(&mut self, index:u16, data:u16) {
         let hi = (data >> 8) as u8;
         let lo = (data & 0x00FF) as u8;
         self.write_byte(index, lo);
-        self.write_byte(index + 1, hi);
+        self.write_byte(index.wrapping_add(1), hi);
     }
 
     pub fn read_word(&mut self, index:u16) -> u16 {
         let lo = self.read_byte(index) as u16;
-        let hi = self.read_byte(index + 1) as u16;
-        return (hi << 8) | lo;
+        let hi = self.read_byte(index.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+}
+
+impl Default for RAM {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
+/**
+ * Flat 64 KiB memory, every address is plain read/write storage.
+ */
+impl Bus for RAM {
+    fn read(&mut self, addr:u16) -> u8 {
+        self.read_byte(addr)
+    }
+
+    fn write(&mut self, addr:u16, data:u8) {
+        self.write_byte(addr, data)
+    }
+
+    fn peek(&self, addr:u16) -> u8 {
+        self.mem_array[addr as usize]
+    }
 }
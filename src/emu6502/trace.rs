@@ -0,0 +1,152 @@
+use std::fmt;
+
+use crate::emu6502::bus::Bus;
+use crate::emu6502::disassembler::{self, Disassembly};
+use crate::emu6502::op_codes::{AddressingMode, Instruction, OP_CODES_TABLE};
+
+/**
+ * CPU state right before an instruction executes.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub program_counter:u16,
+    pub opcode:u8,
+    /* Operand bytes following the opcode, only operand_count of them are valid */
+    pub operands:[u8; 2],
+    pub operand_count:u8,
+    pub register_a:u8,
+    pub register_x:u8,
+    pub register_y:u8,
+    pub status:u8,
+    pub stack_pointer:u8,
+    /* Cycles executed before the instruction */
+    pub cycles:u64
+}
+
+impl TraceEvent {
+    /**
+     * Valid operand bytes.
+     */
+    pub fn operands(&self) -> &[u8] {
+        &self.operands[..self.operand_count as usize]
+    }
+
+    /**
+     * Decodes the traced instruction from the captured bytes.
+     */
+    pub fn disassemble(&self) -> Disassembly {
+        disassembler::decode(self.program_counter, |addr| match addr.wrapping_sub(self.program_counter) {
+            0 => Some(self.opcode),
+            offset => self.operands().get(offset as usize - 1).copied()
+        })
+    }
+
+    /**
+     * Formats as a Nintendulator log line, the format of nestest.log. Memory
+     * operands show the effective address and the value there, peeked from
+     * the bus, and the PPU scanline and dot come before the cycle count.
+     */
+    pub fn nintendulator<B: Bus + ?Sized>(&self, bus: &B, scanline:u16, dot:u16) -> String {
+        let disassembly = self.disassemble();
+        let bytes = disassembly.bytes().iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ");
+        // unofficial opcodes take the place of the leading space with '*'
+        let mut text = if disassembly.unofficial { disassembly.to_string() } else { format!(" {}", disassembly) };
+        text.push_str(&self.memory_operand(bus));
+        format!("{:04X}  {:<8} {:<33}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+            self.program_counter, bytes, text, self.register_a, self.register_x, self.register_y, self.status,
+            self.stack_pointer, scanline, dot, self.cycles)
+    }
+
+    /**
+     * Effective address and value Nintendulator appends to the operand.
+     */
+    fn memory_operand<B: Bus + ?Sized>(&self, bus: &B) -> String {
+        let op_code = &OP_CODES_TABLE[self.opcode as usize];
+        let byte = self.operands[0];
+        let word = u16::from_le_bytes(self.operands);
+        // zero page pointers wrap within the zero page
+        let zero_page_word = |addr:u8| u16::from_le_bytes([bus.peek(addr as u16), bus.peek(addr.wrapping_add(1) as u16)]);
+        match op_code.mode {
+            AddressingMode::ZeroPage => format!(" = {:02X}", bus.peek(byte as u16)),
+            AddressingMode::ZeroPage_X | AddressingMode::ZeroPage_Y => {
+                let index = if op_code.mode == AddressingMode::ZeroPage_X { self.register_x } else { self.register_y };
+                let addr = byte.wrapping_add(index);
+                format!(" @ {:02X} = {:02X}", addr, bus.peek(addr as u16))
+            },
+            AddressingMode::Absolute => match op_code.instruction {
+                Instruction::JMP | Instruction::JSR => String::new(),
+                _ => format!(" = {:02X}", bus.peek(word))
+            },
+            AddressingMode::Absolute_X | AddressingMode::Absolute_Y => {
+                let index = if op_code.mode == AddressingMode::Absolute_X { self.register_x } else { self.register_y };
+                let addr = word.wrapping_add(index as u16);
+                format!(" @ {:04X} = {:02X}", addr, bus.peek(addr))
+            },
+            AddressingMode::Indirect => {
+                // the pointer high byte does not carry into the next page
+                let high = (word & 0xFF00) | (word.wrapping_add(1) & 0x00FF);
+                format!(" = {:04X}", u16::from_le_bytes([bus.peek(word), bus.peek(high)]))
+            },
+            AddressingMode::Indirect_X => {
+                let pointer = byte.wrapping_add(self.register_x);
+                let addr = zero_page_word(pointer);
+                format!(" @ {:02X} = {:04X} = {:02X}", pointer, addr, bus.peek(addr))
+            },
+            AddressingMode::Indirect_Y => {
+                let base = zero_page_word(byte);
+                let addr = base.wrapping_add(self.register_y as u16);
+                format!(" = {:04X} @ {:04X} = {:02X}", base, addr, bus.peek(addr))
+            },
+            _ => String::new()
+        }
+    }
+}
+
+impl fmt::Display for TraceEvent {
+    /**
+     * Formats as "C000  4C F5 C5  JMP $C5F5     A:00 X:00 Y:00 P:24 SP:FD CYC:7".
+     */
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut bytes = format!("{:02X}", self.opcode);
+        for operand in self.operands() {
+            bytes.push_str(&format!(" {:02X}", operand));
+        }
+        write!(f, "{:04X}  {:<8}  {:<12}  A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+            self.program_counter, bytes, self.disassemble().to_string(), self.register_a, self.register_x, self.register_y,
+            self.status, self.stack_pointer, self.cycles)
+    }
+}
+
+/**
+ * Conditions the CPU reports besides executed instructions.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diagnostic {
+    /* The opcode is unofficial while unofficial opcodes are disabled */
+    IllegalOpcode { program_counter:u16, opcode:u8 },
+    /* A KIL/JAM opcode halted the CPU */
+    Jammed { program_counter:u16, opcode:u8 }
+}
+
+/**
+ * Observer for CPU execution, set through CPU::tracer. Nothing is collected
+ * while no tracer is set. Closures taking a &TraceEvent are tracers that
+ * only see instructions.
+ */
+pub trait Tracer {
+    /**
+     * Called before each instruction executes.
+     */
+    fn instruction(&mut self, _event: &TraceEvent) {}
+
+    /**
+     * Called when the CPU runs into a condition it cannot execute.
+     */
+    fn diagnostic(&mut self, _diagnostic: &Diagnostic) {}
+}
+
+impl<F: FnMut(&TraceEvent)> Tracer for F {
+    fn instruction(&mut self, event: &TraceEvent) {
+        self(event)
+    }
+}
@@ -1 +1,8 @@
-pub mod emu6502;
\ No newline at end of file
+pub mod apu;
+pub mod bus;
+pub mod cartridge;
+pub mod controller;
+pub mod emu6502;
+pub mod mapper;
+pub mod nes;
+pub mod ppu;
\ No newline at end of file
@@ -1,6 +1,26 @@
+use std::env;
+use std::process;
+
+use nesguin::cartridge::Cartridge;
 use nesguin::emu6502::cpu::CPU;
+use nesguin::emu6502::disassembler::disassemble_bytes;
+
+/* PRG ROM is listed in 16 KiB banks */
+const PRG_BANK_SIZE:usize = 0x4000;
 
 fn main() {
+    let args:Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("disasm") => disassemble_rom(&args[2..]),
+        Some(_) => {
+            eprintln!("usage: nesguin [disasm <rom.nes> [bank]]");
+            process::exit(2);
+        },
+        None => run_demo()
+    }
+}
+
+fn run_demo() {
     println!("Starting emulation...");
     println!("Loading program...");
     let program:Vec<u8> = vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00];
@@ -8,3 +28,38 @@ fn main() {
     cpu.load_program(program);
     cpu.run();
 }
+
+/**
+ * Lists the PRG ROM banks of an iNES file, or a single bank when its number
+ * is given. The last bank is shown at $C000 where it is usually fixed, the
+ * others at $8000.
+ */
+fn disassemble_rom(args:&[String]) {
+    let Some(path) = args.first() else {
+        eprintln!("usage: nesguin disasm <rom.nes> [bank]");
+        process::exit(2);
+    };
+    let cartridge = Cartridge::load(path).unwrap_or_else(|err| {
+        eprintln!("{}: {}", path, err);
+        process::exit(1);
+    });
+    let banks:Vec<&[u8]> = cartridge.prg_rom.chunks(PRG_BANK_SIZE).collect();
+    let selected = match args.get(1) {
+        Some(bank) => match bank.parse::<usize>() {
+            Ok(bank) if bank < banks.len() => bank..bank + 1,
+            _ => {
+                eprintln!("bank must be a number below {}", banks.len());
+                process::exit(2);
+            }
+        },
+        None => 0..banks.len()
+    };
+    for bank in selected {
+        let origin = if bank == banks.len() - 1 { 0xC000 } else { 0x8000 };
+        println!("; PRG bank {} at ${:04X}", bank, origin);
+        for line in disassemble_bytes(banks[bank], origin) {
+            println!("{}", line.listing());
+        }
+        println!();
+    }
+}
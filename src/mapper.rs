@@ -0,0 +1,157 @@
+pub mod nrom;
+pub mod mmc1;
+pub mod mmc3;
+pub mod uxrom;
+pub mod cnrom;
+pub mod axrom;
+pub mod ram_cartridge;
+
+use crate::cartridge::{Cartridge, CartridgeError, Mirroring};
+use crate::mapper::axrom::AxROM;
+use crate::mapper::cnrom::CNROM;
+use crate::mapper::mmc1::MMC1;
+use crate::mapper::mmc3::MMC3;
+use crate::mapper::nrom::NROM;
+use crate::mapper::uxrom::UxROM;
+
+/**
+ * Cartridge board logic. The CPU bus forwards $4020-$FFFF here and the PPU
+ * forwards pattern table accesses at $0000-$1FFF.
+ */
+pub trait Mapper {
+    /**
+     * Reads from CPU cartridge space, returns None when nothing drives the bus.
+     */
+    fn cpu_read(&mut self, addr:u16) -> Option<u8> {
+        self.cpu_peek(addr)
+    }
+
+    /**
+     * Reads from CPU cartridge space without side effects.
+     */
+    fn cpu_peek(&self, addr:u16) -> Option<u8>;
+
+    /**
+     * Writes to CPU cartridge space, usually a bank select register.
+     */
+    fn cpu_write(&mut self, addr:u16, data:u8);
+
+    /**
+     * Reads from the pattern tables.
+     */
+    fn ppu_read(&mut self, addr:u16) -> u8 {
+        self.ppu_peek(addr)
+    }
+
+    /**
+     * Reads from the pattern tables without side effects.
+     */
+    fn ppu_peek(&self, addr:u16) -> u8;
+
+    /**
+     * Writes to the pattern tables, ignored unless the board has CHR RAM.
+     */
+    fn ppu_write(&mut self, addr:u16, data:u8);
+
+    /**
+     * Current nametable mirroring.
+     */
+    fn mirroring(&self) -> Mirroring;
+
+    /**
+     * State of the cartridge IRQ line, true when asserted.
+     */
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /**
+     * Called as CPU cycles pass, for boards that time CPU bus activity.
+     */
+    fn cpu_clock(&mut self, _cycles:u8) {}
+
+    /**
+     * Called with every address the PPU puts on its bus, including nametable
+     * fetches and $2006/$2007 accesses, along with the PPU cycle count.
+     */
+    fn ppu_address(&mut self, _addr:u16, _ppu_cycle:u64) {}
+}
+
+/**
+ * Creates the mapper for the board described by the cartridge.
+ */
+pub fn from_cartridge(cartridge: Cartridge) -> Result<Box<dyn Mapper>, CartridgeError> {
+    match cartridge.mapper {
+        0 => Ok(Box::new(NROM::new(cartridge))),
+        1 => Ok(Box::new(MMC1::new(cartridge))),
+        2 => Ok(Box::new(UxROM::new(cartridge))),
+        3 => Ok(Box::new(CNROM::new(cartridge))),
+        4 => Ok(Box::new(MMC3::new(cartridge))),
+        7 => Ok(Box::new(AxROM::new(cartridge))),
+        mapper => Err(CartridgeError::UnsupportedMapper(mapper))
+    }
+}
+
+/**
+ * Whether a discrete logic board has bus conflicts. NES 2.0 submapper 1 means
+ * no conflicts and submapper 2 means AND-type conflicts, otherwise the board
+ * default is used.
+ */
+pub(crate) fn has_bus_conflicts(cartridge: &Cartridge, default:bool) -> bool {
+    match cartridge.submapper {
+        1 => false,
+        2 => true,
+        _ => default
+    }
+}
+
+/**
+ * Value a register write on a discrete logic board latches. With bus
+ * conflicts the PRG ROM drives the data bus at the same time as the CPU,
+ * and the latch sees both values ANDed together.
+ */
+pub(crate) fn bus_conflict(conflicts:bool, data:u8, rom_data:Option<u8>) -> u8 {
+    match (conflicts, rom_data) {
+        (true, Some(rom_data)) => data & rom_data,
+        _ => data
+    }
+}
+
+/**
+ * Reads a byte from a banked memory, bank numbers wrap around the memory size.
+ */
+pub(crate) fn read_banked(memory: &[u8], bank_size:usize, bank:usize, offset:usize) -> u8 {
+    let bank_count = (memory.len() / bank_size).max(1);
+    memory[((bank % bank_count) * bank_size + offset) % memory.len()]
+}
+
+/**
+ * Pattern table memory, CHR ROM or CHR RAM when the cartridge has none.
+ */
+pub(crate) struct ChrMemory {
+    pub data:Vec<u8>,
+    pub writable:bool
+}
+
+impl ChrMemory {
+    pub fn new(cartridge: &Cartridge) -> Self {
+        if cartridge.has_chr_ram() {
+            let size = (cartridge.chr_ram_size + cartridge.chr_nvram_size).max(0x2000);
+            ChrMemory { data: vec![0; size], writable: true }
+        } else {
+            ChrMemory { data: cartridge.chr_rom.clone(), writable: false }
+        }
+    }
+
+    pub fn read(&self, bank_size:usize, bank:usize, offset:usize) -> u8 {
+        read_banked(&self.data, bank_size, bank, offset)
+    }
+
+    pub fn write(&mut self, bank_size:usize, bank:usize, offset:usize, data:u8) {
+        if self.writable {
+            let bank_count = (self.data.len() / bank_size).max(1);
+            let index = ((bank % bank_count) * bank_size + offset) % self.data.len();
+            self.data[index] = data;
+        }
+    }
+}
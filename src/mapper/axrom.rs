@@ -0,0 +1,64 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{bus_conflict, has_bus_conflicts, read_banked, ChrMemory, Mapper};
+
+const PRG_BANK_SIZE:usize = 0x8000;
+
+/**
+ * AxROM (mapper 7), switchable 32 KiB PRG bank and single screen mirroring.
+ * A write to $8000-$FFFF selects the PRG bank with bits 0-3 and the
+ * nametable with bit 4. 8 KiB CHR RAM.
+ */
+#[allow(clippy::upper_case_acronyms)]
+pub struct AxROM {
+    prg_rom:Vec<u8>,
+    chr:ChrMemory,
+    bus_conflicts:bool,
+    prg_bank:usize,
+    mirroring:Mirroring
+}
+
+impl AxROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        AxROM {
+            chr: ChrMemory::new(&cartridge),
+            // ANROM has no bus conflicts, AMROM and AOROM do
+            bus_conflicts: has_bus_conflicts(&cartridge, false),
+            prg_rom: cartridge.prg_rom,
+            prg_bank: 0,
+            mirroring: Mirroring::SingleScreenLower
+        }
+    }
+}
+
+impl Mapper for AxROM {
+    fn cpu_peek(&self, addr:u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(read_banked(&self.prg_rom, PRG_BANK_SIZE, self.prg_bank, addr as usize & 0x7FFF)),
+            _ => None
+        }
+    }
+
+    fn cpu_write(&mut self, addr:u16, data:u8) {
+        if addr >= 0x8000 {
+            let data = bus_conflict(self.bus_conflicts, data, self.cpu_peek(addr));
+            self.prg_bank = (data & 0x0F) as usize;
+            self.mirroring = if data & 0x10 != 0 {
+                Mirroring::SingleScreenUpper
+            } else {
+                Mirroring::SingleScreenLower
+            };
+        }
+    }
+
+    fn ppu_peek(&self, addr:u16) -> u8 {
+        self.chr.read(0x2000, 0, addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr:u16, data:u8) {
+        self.chr.write(0x2000, 0, addr as usize & 0x1FFF, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
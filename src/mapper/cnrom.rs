@@ -0,0 +1,57 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{bus_conflict, has_bus_conflicts, read_banked, ChrMemory, Mapper};
+
+const CHR_BANK_SIZE:usize = 0x2000;
+
+/**
+ * CNROM (mapper 3), fixed 16 or 32 KiB PRG ROM and a switchable 8 KiB CHR
+ * bank selected by any write to $8000-$FFFF.
+ */
+#[allow(clippy::upper_case_acronyms)]
+pub struct CNROM {
+    prg_rom:Vec<u8>,
+    chr:ChrMemory,
+    mirroring:Mirroring,
+    bus_conflicts:bool,
+    chr_bank:usize
+}
+
+impl CNROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        CNROM {
+            chr: ChrMemory::new(&cartridge),
+            bus_conflicts: has_bus_conflicts(&cartridge, true),
+            prg_rom: cartridge.prg_rom,
+            mirroring: cartridge.mirroring,
+            chr_bank: 0
+        }
+    }
+}
+
+impl Mapper for CNROM {
+    fn cpu_peek(&self, addr:u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xFFFF => Some(read_banked(&self.prg_rom, 0x8000, 0, addr as usize - 0x8000)),
+            _ => None
+        }
+    }
+
+    fn cpu_write(&mut self, addr:u16, data:u8) {
+        if addr >= 0x8000 {
+            let data = bus_conflict(self.bus_conflicts, data, self.cpu_peek(addr));
+            self.chr_bank = data as usize;
+        }
+    }
+
+    fn ppu_peek(&self, addr:u16) -> u8 {
+        self.chr.read(CHR_BANK_SIZE, self.chr_bank, addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr:u16, data:u8) {
+        self.chr.write(CHR_BANK_SIZE, self.chr_bank, addr as usize & 0x1FFF, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
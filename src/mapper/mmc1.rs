@@ -0,0 +1,168 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{read_banked, ChrMemory, Mapper};
+
+const PRG_BANK_SIZE:usize = 0x4000;
+const CHR_BANK_SIZE:usize = 0x1000;
+const PRG_RAM_BANK_SIZE:usize = 0x2000;
+/* Shift register is empty when the marker bit reaches bit 0 */
+const SHIFT_RESET:u8 = 0x10;
+
+/**
+ * MMC1 (mapper 1). Registers are loaded one bit at a time through a serial
+ * shift register, five writes to $8000-$FFFF fill it and the address of the
+ * fifth write selects the register:
+ *
+ * $8000-$9FFF  Control, mirroring, PRG mode and CHR mode
+ * $A000-$BFFF  CHR bank 0
+ * $C000-$DFFF  CHR bank 1
+ * $E000-$FFFF  PRG bank and PRG RAM disable
+ *
+ * Boards with 8 KiB CHR RAM reuse CHR bank 0 for large memories, bit 4
+ * selects the 256 KiB PRG ROM half on SUROM/SXROM and bits 2-3 select the
+ * 8 KiB PRG RAM bank on SOROM/SXROM.
+ */
+#[allow(clippy::upper_case_acronyms)]
+pub struct MMC1 {
+    prg_rom:Vec<u8>,
+    prg_ram:Vec<u8>,
+    chr:ChrMemory,
+    shift_register:u8,
+    control:u8,
+    chr_bank_0:u8,
+    chr_bank_1:u8,
+    prg_bank:u8,
+    /* CPU cycles since the last serial write */
+    cycles_since_write:u8
+}
+
+impl MMC1 {
+    pub fn new(cartridge: Cartridge) -> Self {
+        MMC1 {
+            chr: ChrMemory::new(&cartridge),
+            prg_ram: vec![0; cartridge.prg_ram_size + cartridge.prg_nvram_size],
+            prg_rom: cartridge.prg_rom,
+            shift_register: SHIFT_RESET,
+            // power on in PRG mode 3, last bank fixed at $C000
+            control: 0x0C,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            cycles_since_write: u8::MAX
+        }
+    }
+
+    /**
+     * 16 KiB PRG bank mapped at the address.
+     */
+    fn prg_bank_at(&self, addr:u16) -> usize {
+        let outer = if self.prg_rom.len() > 0x40000 { (self.chr_bank_0 & 0x10) as usize } else { 0 };
+        let bank = (self.prg_bank & 0x0F) as usize;
+        let upper_half = addr >= 0xC000;
+        match (self.control >> 2) & 0x03 {
+            0 | 1 => outer | (bank & 0x0E) | upper_half as usize,
+            2 => if upper_half { outer | bank } else { outer },
+            _ => if upper_half { outer | 0x0F } else { outer | bank }
+        }
+    }
+
+    /**
+     * 4 KiB CHR bank mapped at the pattern table address.
+     */
+    fn chr_bank_at(&self, addr:u16) -> usize {
+        let upper_table = addr & 0x1000 != 0;
+        if self.control & 0x10 == 0 {
+            (self.chr_bank_0 & 0x1E) as usize | upper_table as usize
+        } else if upper_table {
+            self.chr_bank_1 as usize
+        } else {
+            self.chr_bank_0 as usize
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        !self.prg_ram.is_empty() && self.prg_bank & 0x10 == 0
+    }
+
+    /**
+     * Index into PRG RAM for an address in $6000-$7FFF.
+     */
+    fn prg_ram_index(&self, addr:u16) -> usize {
+        let bank = match self.prg_ram.len() {
+            0x4000 => (self.chr_bank_0 >> 3) & 0x01,
+            0x8000 => (self.chr_bank_0 >> 2) & 0x03,
+            _ => 0
+        } as usize;
+        (bank * PRG_RAM_BANK_SIZE + (addr as usize & 0x1FFF)) % self.prg_ram.len()
+    }
+
+    fn write_register(&mut self, addr:u16, data:u8) {
+        match addr {
+            0x8000..=0x9FFF => self.control = data,
+            0xA000..=0xBFFF => self.chr_bank_0 = data,
+            0xC000..=0xDFFF => self.chr_bank_1 = data,
+            _ => self.prg_bank = data
+        }
+    }
+}
+
+impl Mapper for MMC1 {
+    fn cpu_peek(&self, addr:u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => Some(self.prg_ram[self.prg_ram_index(addr)]),
+            0x8000..=0xFFFF => Some(read_banked(&self.prg_rom, PRG_BANK_SIZE, self.prg_bank_at(addr), addr as usize & 0x3FFF)),
+            _ => None
+        }
+    }
+
+    fn cpu_write(&mut self, addr:u16, data:u8) {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                let index = self.prg_ram_index(addr);
+                self.prg_ram[index] = data;
+            },
+            0x8000..=0xFFFF => {
+                // the serial port ignores a write on the cycle right after another,
+                // such as the two writes of a read-modify-write instruction
+                if self.cycles_since_write <= 1 {
+                    return;
+                }
+                self.cycles_since_write = 0;
+                if data & 0x80 != 0 {
+                    self.shift_register = SHIFT_RESET;
+                    self.control |= 0x0C;
+                    return;
+                }
+                let full = self.shift_register & 0x01 != 0;
+                self.shift_register = (self.shift_register >> 1) | ((data & 0x01) << 4);
+                if full {
+                    let value = self.shift_register;
+                    self.write_register(addr, value);
+                    self.shift_register = SHIFT_RESET;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn ppu_peek(&self, addr:u16) -> u8 {
+        self.chr.read(CHR_BANK_SIZE, self.chr_bank_at(addr), addr as usize & 0x0FFF)
+    }
+
+    fn ppu_write(&mut self, addr:u16, data:u8) {
+        let bank = self.chr_bank_at(addr);
+        self.chr.write(CHR_BANK_SIZE, bank, addr as usize & 0x0FFF, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal
+        }
+    }
+
+    fn cpu_clock(&mut self, cycles:u8) {
+        self.cycles_since_write = self.cycles_since_write.saturating_add(cycles);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{read_banked, ChrMemory, Mapper};
+
+const PRG_BANK_SIZE:usize = 0x2000;
+const CHR_BANK_SIZE:usize = 0x0400;
+/* A12 has to stay low this many PPU cycles before a rising edge clocks the counter */
+const A12_FILTER_CYCLES:u64 = 10;
+
+/**
+ * MMC3 chip revision, they differ in how a counter reload to zero behaves.
+ */
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MMC3Revision {
+    /* MMC3B/MMC3C, the IRQ fires on every clock that leaves the counter at zero */
+    Sharp,
+    /* MMC3A, the IRQ fires only when the counter is decremented to zero or after a $C001 reload */
+    NEC
+}
+
+/**
+ * MMC3 (mapper 4), TxROM boards.
+ *
+ * $8000-$9FFF  Bank select (even), bank data (odd)
+ * $A000-$BFFF  Mirroring (even), PRG RAM protect (odd)
+ * $C000-$DFFF  IRQ latch (even), IRQ reload (odd)
+ * $E000-$FFFF  IRQ disable (even), IRQ enable (odd)
+ *
+ * The scanline counter is clocked by rising edges of PPU A12 that follow a
+ * long enough low period, which filters out the short pulses between sprite
+ * pattern fetches.
+ */
+#[allow(clippy::upper_case_acronyms)]
+pub struct MMC3 {
+    prg_rom:Vec<u8>,
+    prg_ram:Vec<u8>,
+    chr:ChrMemory,
+    revision:MMC3Revision,
+    four_screen:bool,
+    bank_select:u8,
+    /* R0-R7 bank registers */
+    banks:[u8; 8],
+    mirroring:Mirroring,
+    prg_ram_enabled:bool,
+    prg_ram_write_protected:bool,
+    irq_latch:u8,
+    irq_counter:u8,
+    irq_reload:bool,
+    irq_enabled:bool,
+    irq_asserted:bool,
+    a12_high:bool,
+    a12_low_since:u64
+}
+
+impl MMC3 {
+    /**
+     * Creates an MMC3, NES 2.0 submapper 4 selects the NEC revision.
+     */
+    pub fn new(cartridge: Cartridge) -> Self {
+        let revision = if cartridge.submapper == 4 { MMC3Revision::NEC } else { MMC3Revision::Sharp };
+        MMC3::with_revision(cartridge, revision)
+    }
+
+    pub fn with_revision(cartridge: Cartridge, revision: MMC3Revision) -> Self {
+        MMC3 {
+            chr: ChrMemory::new(&cartridge),
+            prg_ram: vec![0; (cartridge.prg_ram_size + cartridge.prg_nvram_size).max(0x2000)],
+            prg_rom: cartridge.prg_rom,
+            revision,
+            four_screen: cartridge.mirroring == Mirroring::FourScreen,
+            bank_select: 0,
+            banks: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring: cartridge.mirroring,
+            prg_ram_enabled: true,
+            prg_ram_write_protected: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_asserted: false,
+            a12_high: false,
+            a12_low_since: 0
+        }
+    }
+
+    /**
+     * 8 KiB PRG bank mapped at the address.
+     */
+    fn prg_bank_at(&self, addr:u16) -> usize {
+        let second_last = (self.prg_rom.len() / PRG_BANK_SIZE).max(2) - 2;
+        let swap_mode = self.bank_select & 0x40 != 0;
+        match (addr >> 13) & 0x03 {
+            0 => if swap_mode { second_last } else { self.banks[6] as usize },
+            1 => self.banks[7] as usize,
+            2 => if swap_mode { self.banks[6] as usize } else { second_last },
+            _ => second_last + 1
+        }
+    }
+
+    /**
+     * 1 KiB CHR bank mapped at the pattern table address.
+     */
+    fn chr_bank_at(&self, addr:u16) -> usize {
+        // CHR inversion swaps the 2 KiB and 1 KiB halves
+        let slot = ((addr >> 10) & 0x07) ^ if self.bank_select & 0x80 != 0 { 0x04 } else { 0 };
+        match slot {
+            0 => (self.banks[0] & 0xFE) as usize,
+            1 => (self.banks[0] | 0x01) as usize,
+            2 => (self.banks[1] & 0xFE) as usize,
+            3 => (self.banks[1] | 0x01) as usize,
+            _ => self.banks[slot as usize - 2] as usize
+        }
+    }
+
+    /**
+     * Boards wired for four screen VRAM ignore the mirroring register.
+     */
+    fn set_mirroring(&mut self, data:u8) {
+        if !self.four_screen {
+            self.mirroring = if data & 0x01 == 0 { Mirroring::Vertical } else { Mirroring::Horizontal };
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        let previous = self.irq_counter;
+        let reloaded = self.irq_reload;
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+        } else {
+            self.irq_counter -= 1;
+        }
+        self.irq_reload = false;
+        let trigger = match self.revision {
+            MMC3Revision::Sharp => self.irq_counter == 0,
+            MMC3Revision::NEC => self.irq_counter == 0 && (previous != 0 || reloaded)
+        };
+        if trigger && self.irq_enabled {
+            self.irq_asserted = true;
+        }
+    }
+}
+
+impl Mapper for MMC3 {
+    fn cpu_peek(&self, addr:u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled => Some(self.prg_ram[addr as usize & 0x1FFF]),
+            0x8000..=0xFFFF => Some(read_banked(&self.prg_rom, PRG_BANK_SIZE, self.prg_bank_at(addr), addr as usize & 0x1FFF)),
+            _ => None
+        }
+    }
+
+    fn cpu_write(&mut self, addr:u16, data:u8) {
+        let even = addr & 0x01 == 0;
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled && !self.prg_ram_write_protected =>
+                self.prg_ram[addr as usize & 0x1FFF] = data,
+            0x8000..=0x9FFF if even => self.bank_select = data,
+            0x8000..=0x9FFF => self.banks[(self.bank_select & 0x07) as usize] = data,
+            0xA000..=0xBFFF if even => self.set_mirroring(data),
+            0xA000..=0xBFFF => {
+                self.prg_ram_enabled = data & 0x80 != 0;
+                self.prg_ram_write_protected = data & 0x40 != 0;
+            },
+            0xC000..=0xDFFF if even => self.irq_latch = data,
+            0xC000..=0xDFFF => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            },
+            0xE000..=0xFFFF if even => {
+                self.irq_enabled = false;
+                self.irq_asserted = false;
+            },
+            0xE000..=0xFFFF => self.irq_enabled = true,
+            _ => {}
+        }
+    }
+
+    fn ppu_peek(&self, addr:u16) -> u8 {
+        self.chr.read(CHR_BANK_SIZE, self.chr_bank_at(addr), addr as usize & 0x03FF)
+    }
+
+    fn ppu_write(&mut self, addr:u16, data:u8) {
+        let bank = self.chr_bank_at(addr);
+        self.chr.write(CHR_BANK_SIZE, bank, addr as usize & 0x03FF, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_asserted
+    }
+
+    fn ppu_address(&mut self, addr:u16, ppu_cycle:u64) {
+        let a12 = addr & 0x1000 != 0;
+        if a12 && !self.a12_high && ppu_cycle.saturating_sub(self.a12_low_since) >= A12_FILTER_CYCLES {
+            self.clock_irq_counter();
+        } else if !a12 && self.a12_high {
+            self.a12_low_since = ppu_cycle;
+        }
+        self.a12_high = a12;
+    }
+}
@@ -0,0 +1,65 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{read_banked, ChrMemory, Mapper};
+
+/**
+ * NROM (mapper 0), 16 or 32 KiB PRG ROM without bank switching. 16 KiB
+ * images are mirrored into $C000-$FFFF. Optional PRG RAM at $6000-$7FFF.
+ */
+#[allow(clippy::upper_case_acronyms)]
+pub struct NROM {
+    prg_rom:Vec<u8>,
+    prg_ram:Vec<u8>,
+    chr:ChrMemory,
+    mirroring:Mirroring
+}
+
+impl NROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        let mut prg_ram = vec![0; cartridge.prg_ram_size + cartridge.prg_nvram_size];
+        // the trainer is mapped at $7000, boards with a trainer have the full 8 KiB
+        if let Some(trainer) = &cartridge.trainer {
+            if prg_ram.len() < 0x2000 {
+                prg_ram.resize(0x2000, 0);
+            }
+            prg_ram[0x1000 .. 0x1000 + trainer.len()].copy_from_slice(trainer);
+        }
+        NROM {
+            chr: ChrMemory::new(&cartridge),
+            prg_rom: cartridge.prg_rom,
+            prg_ram,
+            mirroring: cartridge.mirroring
+        }
+    }
+}
+
+impl Mapper for NROM {
+    fn cpu_peek(&self, addr:u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if !self.prg_ram.is_empty() =>
+                Some(self.prg_ram[(addr as usize - 0x6000) % self.prg_ram.len()]),
+            0x8000..=0xFFFF => Some(read_banked(&self.prg_rom, 0x8000, 0, addr as usize - 0x8000)),
+            _ => None
+        }
+    }
+
+    fn cpu_write(&mut self, addr:u16, data:u8) {
+        if let 0x6000..=0x7FFF = addr {
+            if !self.prg_ram.is_empty() {
+                let len = self.prg_ram.len();
+                self.prg_ram[(addr as usize - 0x6000) % len] = data;
+            }
+        }
+    }
+
+    fn ppu_peek(&self, addr:u16) -> u8 {
+        self.chr.read(0x2000, 0, addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr:u16, data:u8) {
+        self.chr.write(0x2000, 0, addr as usize & 0x1FFF, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
@@ -0,0 +1,53 @@
+use crate::cartridge::Mirroring;
+use crate::mapper::Mapper;
+
+const CARTRIDGE_START:usize = 0x4020;
+
+/**
+ * Stand-in board used while no cartridge is inserted. All of cartridge space
+ * $4020-$FFFF is RAM so raw programs can be loaded and run, and the pattern
+ * tables are 8 KiB of CHR RAM.
+ */
+pub struct RamCartridge {
+    prg_ram:Vec<u8>,
+    chr_ram:Vec<u8>
+}
+
+impl RamCartridge {
+    pub fn new() -> Self {
+        RamCartridge {
+            prg_ram: vec![0; 0x10000 - CARTRIDGE_START],
+            chr_ram: vec![0; 0x2000]
+        }
+    }
+}
+
+impl Default for RamCartridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mapper for RamCartridge {
+    fn cpu_peek(&self, addr:u16) -> Option<u8> {
+        (addr as usize).checked_sub(CARTRIDGE_START).map(|index| self.prg_ram[index])
+    }
+
+    fn cpu_write(&mut self, addr:u16, data:u8) {
+        if let Some(index) = (addr as usize).checked_sub(CARTRIDGE_START) {
+            self.prg_ram[index] = data;
+        }
+    }
+
+    fn ppu_peek(&self, addr:u16) -> u8 {
+        self.chr_ram[addr as usize & 0x1FFF]
+    }
+
+    fn ppu_write(&mut self, addr:u16, data:u8) {
+        self.chr_ram[addr as usize & 0x1FFF] = data;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::Horizontal
+    }
+}
@@ -0,0 +1,60 @@
+use crate::cartridge::{Cartridge, Mirroring};
+use crate::mapper::{bus_conflict, has_bus_conflicts, read_banked, ChrMemory, Mapper};
+
+const PRG_BANK_SIZE:usize = 0x4000;
+
+/**
+ * UxROM (mapper 2), switchable 16 KiB PRG bank at $8000 and the last bank
+ * fixed at $C000. Any write to $8000-$FFFF selects the bank, 8 KiB CHR RAM.
+ */
+#[allow(clippy::upper_case_acronyms)]
+pub struct UxROM {
+    prg_rom:Vec<u8>,
+    chr:ChrMemory,
+    mirroring:Mirroring,
+    bus_conflicts:bool,
+    prg_bank:usize,
+    last_bank:usize
+}
+
+impl UxROM {
+    pub fn new(cartridge: Cartridge) -> Self {
+        UxROM {
+            chr: ChrMemory::new(&cartridge),
+            bus_conflicts: has_bus_conflicts(&cartridge, true),
+            last_bank: (cartridge.prg_rom.len() / PRG_BANK_SIZE).max(1) - 1,
+            prg_rom: cartridge.prg_rom,
+            mirroring: cartridge.mirroring,
+            prg_bank: 0
+        }
+    }
+}
+
+impl Mapper for UxROM {
+    fn cpu_peek(&self, addr:u16) -> Option<u8> {
+        match addr {
+            0x8000..=0xBFFF => Some(read_banked(&self.prg_rom, PRG_BANK_SIZE, self.prg_bank, addr as usize & 0x3FFF)),
+            0xC000..=0xFFFF => Some(read_banked(&self.prg_rom, PRG_BANK_SIZE, self.last_bank, addr as usize & 0x3FFF)),
+            _ => None
+        }
+    }
+
+    fn cpu_write(&mut self, addr:u16, data:u8) {
+        if addr >= 0x8000 {
+            let data = bus_conflict(self.bus_conflicts, data, self.cpu_peek(addr));
+            self.prg_bank = data as usize;
+        }
+    }
+
+    fn ppu_peek(&self, addr:u16) -> u8 {
+        self.chr.read(0x2000, 0, addr as usize & 0x1FFF)
+    }
+
+    fn ppu_write(&mut self, addr:u16, data:u8) {
+        self.chr.write(0x2000, 0, addr as usize & 0x1FFF, data);
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+}
@@ -0,0 +1,184 @@
+use crate::bus::NesBus;
+use crate::cartridge::{Cartridge, CartridgeError, Timing};
+use crate::controller::Button;
+use crate::emu6502::cpu::{StepResult, StopReason, CPU};
+use crate::mapper::{self, Mapper};
+
+/**
+ * Console timing. All chips run off one master clock, the CPU and PPU
+ * divide it down by different amounts: NTSC gives 3 PPU dots per CPU cycle
+ * and PAL 3.2.
+ */
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    NTSC,
+    PAL
+}
+
+impl Region {
+    /**
+     * Region of the console a cartridge was made for, multi-region games run as NTSC.
+     */
+    pub fn from_timing(timing: Timing) -> Self {
+        match timing {
+            Timing::PAL => Region::PAL,
+            _ => Region::NTSC
+        }
+    }
+
+    pub fn master_clock_rate(&self) -> f64 {
+        match self {
+            Region::NTSC => 21_477_272.0,
+            Region::PAL => 26_601_712.0
+        }
+    }
+
+    /**
+     * Master clock ticks per CPU cycle.
+     */
+    pub fn cpu_divider(&self) -> u64 {
+        match self {
+            Region::NTSC => 12,
+            Region::PAL => 16
+        }
+    }
+
+    /**
+     * Master clock ticks per PPU dot.
+     */
+    pub fn ppu_divider(&self) -> u64 {
+        match self {
+            Region::NTSC => 4,
+            Region::PAL => 5
+        }
+    }
+
+    pub fn cpu_clock_rate(&self) -> f64 {
+        self.master_clock_rate() / self.cpu_divider() as f64
+    }
+
+    pub fn scanlines_per_frame(&self) -> u16 {
+        match self {
+            Region::NTSC => 262,
+            Region::PAL => 312
+        }
+    }
+}
+
+/**
+ * The console. The CPU drives the system: before each of its reads and
+ * writes its bus clocks the APU and catches the PPU up through the master
+ * clock, and the cycles without an access are clocked at the end of the
+ * instruction. DMA halts the CPU on its next read, interrupt lines are
+ * sampled and serviced between instructions.
+ */
+pub struct Nes {
+    pub cpu:CPU<NesBus>
+}
+
+impl Nes {
+    /**
+     * Creates a powered on console without a cartridge.
+     */
+    pub fn new(region: Region) -> Self {
+        Nes::power_on(NesBus::new(), region)
+    }
+
+    /**
+     * Creates a console for the region the cartridge was made for, with the cartridge inserted.
+     */
+    pub fn with_cartridge(cartridge: Cartridge) -> Result<Self, CartridgeError> {
+        let region = Region::from_timing(cartridge.timing);
+        let mut bus = NesBus::new();
+        bus.insert_cartridge(mapper::from_cartridge(cartridge)?);
+        Ok(Nes::power_on(bus, region))
+    }
+
+    fn power_on(mut bus: NesBus, region: Region) -> Self {
+        bus.set_region(region);
+        let mut nes = Nes { cpu: CPU::with_bus(bus) };
+        nes.reset();
+        nes
+    }
+
+    /**
+     * Inserts a cartridge board and presses reset.
+     */
+    pub fn insert_cartridge(&mut self, mapper: Box<dyn Mapper>) {
+        self.cpu.memory.insert_cartridge(mapper);
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+    }
+
+    pub fn region(&self) -> Region {
+        self.cpu.memory.region()
+    }
+
+    pub fn bus(&self) -> &NesBus {
+        &self.cpu.memory
+    }
+
+    pub fn bus_mut(&mut self) -> &mut NesBus {
+        &mut self.cpu.memory
+    }
+
+    /**
+     * Runs at least the given number of CPU cycles, finishing the instruction in progress.
+     */
+    pub fn run_cycles(&mut self, cycles:u64) -> StopReason {
+        let target = self.cpu.cycles + cycles;
+        while self.cpu.cycles < target {
+            if let Some(reason) = self.step() {
+                return reason;
+            }
+        }
+        StopReason::ConditionMet
+    }
+
+    /**
+     * Runs until the PPU finishes a frame, when vblank starts.
+     */
+    pub fn run_frame(&mut self) -> StopReason {
+        self.cpu.memory.ppu.frame_complete = false;
+        while !self.cpu.memory.ppu.frame_complete {
+            if let Some(reason) = self.step() {
+                return reason;
+            }
+        }
+        StopReason::ConditionMet
+    }
+
+    fn step(&mut self) -> Option<StopReason> {
+        match self.cpu.step() {
+            StepResult::Jammed => Some(StopReason::Jammed),
+            StepResult::IllegalOpcode(code) => Some(StopReason::IllegalOpcode(code)),
+            _ => None
+        }
+    }
+
+    /**
+     * Palette indices of the last finished frame, 256x240 row by row.
+     */
+    pub fn frame_buffer(&self) -> &[u8] {
+        &self.cpu.memory.ppu.frame_buffer
+    }
+
+    /**
+     * Presses or releases a button on the controller in port 0 or 1.
+     */
+    pub fn set_button(&mut self, port:usize, button:Button, pressed:bool) {
+        self.cpu.memory.set_button(port, button, pressed);
+    }
+
+    /**
+     * Resamples the audio produced since the last call and takes the samples.
+     */
+    pub fn take_audio_samples(&mut self) -> Vec<f32> {
+        self.cpu.memory.apu.end_audio_frame();
+        self.cpu.memory.apu.resampler.take_samples()
+    }
+}
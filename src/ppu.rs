@@ -0,0 +1,739 @@
+use crate::cartridge::Mirroring;
+use crate::mapper::Mapper;
+use crate::nes::Region;
+
+pub const DOTS_PER_SCANLINE:u16 = 341;
+/* NTSC frame, PAL has 312 scanlines */
+pub const SCANLINES_PER_FRAME:u16 = 262;
+pub const VBLANK_SCANLINE:u16 = 241;
+pub const PRE_RENDER_SCANLINE:u16 = 261;
+pub const SCREEN_WIDTH:usize = 256;
+pub const SCREEN_HEIGHT:usize = 240;
+const MAX_SPRITES_PER_LINE:usize = 8;
+
+/**
+ * PPUCTRL ($2000) bits
+ */
+pub enum ControlFlag {
+    /* VRAM address increment, 0: add 1, 1: add 32 */
+    VramIncrement,
+    /* Sprite pattern table for 8x8 sprites */
+    SpriteTable,
+    /* Background pattern table */
+    BackgroundTable,
+    /* 0: 8x8 sprites, 1: 8x16 sprites */
+    SpriteSize,
+    /* Generate an NMI at the start of vblank */
+    NmiEnable
+}
+
+impl ControlFlag {
+    pub fn mask(&self) -> u8 {
+        match self {
+            ControlFlag::VramIncrement => 0b0000_0100,
+            ControlFlag::SpriteTable => 0b0000_1000,
+            ControlFlag::BackgroundTable => 0b0001_0000,
+            ControlFlag::SpriteSize => 0b0010_0000,
+            ControlFlag::NmiEnable => 0b1000_0000
+        }
+    }
+}
+
+/**
+ * PPUMASK ($2001) bits
+ */
+pub enum MaskFlag {
+    Grayscale,
+    /* Show background in the leftmost 8 pixels */
+    BackgroundLeft,
+    /* Show sprites in the leftmost 8 pixels */
+    SpritesLeft,
+    ShowBackground,
+    ShowSprites
+}
+
+impl MaskFlag {
+    pub fn mask(&self) -> u8 {
+        match self {
+            MaskFlag::Grayscale => 0b0000_0001,
+            MaskFlag::BackgroundLeft => 0b0000_0010,
+            MaskFlag::SpritesLeft => 0b0000_0100,
+            MaskFlag::ShowBackground => 0b0000_1000,
+            MaskFlag::ShowSprites => 0b0001_0000
+        }
+    }
+}
+
+/**
+ * PPUSTATUS ($2002) bits
+ */
+pub enum StatusFlag {
+    SpriteOverflow,
+    SpriteZeroHit,
+    VerticalBlank
+}
+
+impl StatusFlag {
+    pub fn mask(&self) -> u8 {
+        match self {
+            StatusFlag::SpriteOverflow => 0b0010_0000,
+            StatusFlag::SpriteZeroHit => 0b0100_0000,
+            StatusFlag::VerticalBlank => 0b1000_0000
+        }
+    }
+}
+
+/**
+ * Sprite fetched for the scanline being drawn.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+struct SpriteLine {
+    x:u8,
+    attributes:u8,
+    /* Pattern bits with horizontal flip already applied */
+    pattern_lo:u8,
+    pattern_hi:u8
+}
+
+/**
+ * Ricoh 2C02 picture processing unit.
+ *
+ * The CPU sees eight registers at $2000-$2007, mirrored through $3FFF.
+ * PPUSCROLL and PPUADDR share the internal "loopy" registers: v is the
+ * current VRAM address, t the temporary address, x the fine X scroll and w
+ * the write toggle shared by both double-write registers.
+ *
+ * The pattern tables at $0000-$1FFF live on the cartridge, every method that
+ * touches PPU memory takes the mapper.
+ *
+ * Rendering follows the real fetch pattern dot by dot, so mid-frame register
+ * writes and mapper IRQs timed on PPU A12 behave as on hardware. Each pixel
+ * is written to the frame buffer as a 6 bit NES palette index.
+ */
+#[allow(clippy::upper_case_acronyms)]
+pub struct PPU {
+    pub ctrl:u8,
+    pub mask:u8,
+    pub status:u8,
+    pub oam_addr:u8,
+    pub oam:[u8; 256],
+    /* Nametable RAM, 4 KiB to cover four screen boards */
+    pub vram:[u8; 0x1000],
+    pub palette:[u8; 32],
+    /* Frame timing, PAL frames have a longer vblank and no skipped dot */
+    pub region:Region,
+    /* Current scanline, 0-239 visible, 241 starts vblank, last line pre-render */
+    pub scanline:u16,
+    pub dot:u16,
+    pub frame:u64,
+    /* Total number of dots clocked since power on */
+    pub cycles:u64,
+    v:u16,
+    t:u16,
+    x:u8,
+    w:bool,
+    /* PPUDATA read buffer */
+    read_buffer:u8,
+    /* Value left on the CPU-PPU data bus by the last register access */
+    io_latch:u8,
+    /* 256x240 palette indices, row by row */
+    pub frame_buffer:Vec<u8>,
+    /* Set when vblank starts, the frame buffer holds a finished frame */
+    pub frame_complete:bool,
+    /* Background tile fetched for the next 8 pixels */
+    next_tile:u8,
+    next_attribute:u8,
+    next_pattern_lo:u8,
+    next_pattern_hi:u8,
+    /* Background shift registers, the high byte is the pixel being drawn */
+    pattern_shift_lo:u16,
+    pattern_shift_hi:u16,
+    attribute_shift_lo:u16,
+    attribute_shift_hi:u16,
+    /* Sprites found by evaluation, as 4 byte OAM entries */
+    secondary_oam:[u8; 32],
+    secondary_count:usize,
+    secondary_has_sprite_zero:bool,
+    /* Sprites fetched for the scanline being drawn */
+    sprites:[SpriteLine; MAX_SPRITES_PER_LINE],
+    sprite_count:usize,
+    sprite_zero_on_line:bool
+}
+
+impl PPU {
+    pub fn new() -> Self {
+        PPU {
+            ctrl: 0,
+            mask: 0,
+            status: 0,
+            oam_addr: 0,
+            oam: [0; 256],
+            vram: [0; 0x1000],
+            palette: [0; 32],
+            region: Region::NTSC,
+            scanline: 0,
+            dot: 0,
+            frame: 0,
+            cycles: 0,
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+            read_buffer: 0,
+            io_latch: 0,
+            frame_buffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_complete: false,
+            next_tile: 0,
+            next_attribute: 0,
+            next_pattern_lo: 0,
+            next_pattern_hi: 0,
+            pattern_shift_lo: 0,
+            pattern_shift_hi: 0,
+            attribute_shift_lo: 0,
+            attribute_shift_hi: 0,
+            secondary_oam: [0xFF; 32],
+            secondary_count: 0,
+            secondary_has_sprite_zero: false,
+            sprites: [SpriteLine::default(); MAX_SPRITES_PER_LINE],
+            sprite_count: 0,
+            sprite_zero_on_line: false
+        }
+    }
+
+    /**
+     * Current VRAM address register v.
+     */
+    pub fn vram_addr(&self) -> u16 {
+        self.v
+    }
+
+    /**
+     * Temporary VRAM address register t.
+     */
+    pub fn temp_vram_addr(&self) -> u16 {
+        self.t
+    }
+
+    pub fn fine_x(&self) -> u8 {
+        self.x
+    }
+
+    pub fn write_toggle(&self) -> bool {
+        self.w
+    }
+
+    /**
+     * State of the NMI output, asserted while in vblank with NMI enabled.
+     */
+    pub fn nmi(&self) -> bool {
+        self.status & StatusFlag::VerticalBlank.mask() != 0 && self.ctrl & ControlFlag::NmiEnable.mask() != 0
+    }
+
+    /**
+     * Reads a register as the CPU does, addr is any mirror in $2000-$3FFF.
+     */
+    pub fn read_register(&mut self, addr:u16, mapper: &mut dyn Mapper) -> u8 {
+        let data = match addr & 0x0007 {
+            2 => {
+                let data = (self.status & 0xE0) | (self.io_latch & 0x1F);
+                self.status &= !StatusFlag::VerticalBlank.mask();
+                self.w = false;
+                data
+            },
+            4 => self.read_oam(),
+            7 => {
+                let addr = self.v & 0x3FFF;
+                let data = if addr >= 0x3F00 {
+                    // palette reads are not buffered, the buffer gets the nametable underneath
+                    self.read_buffer = self.read_vram(addr - 0x1000, mapper);
+                    (self.read_palette(addr) & 0x3F) | (self.io_latch & 0xC0)
+                } else {
+                    let data = self.read_buffer;
+                    self.read_buffer = self.read_vram(addr, mapper);
+                    data
+                };
+                self.increment_vram_addr(mapper);
+                data
+            },
+            // write only registers return the latch
+            _ => self.io_latch
+        };
+        self.io_latch = data;
+        data
+    }
+
+    /**
+     * Reads a register without side effects.
+     */
+    pub fn peek_register(&self, addr:u16) -> u8 {
+        match addr & 0x0007 {
+            2 => (self.status & 0xE0) | (self.io_latch & 0x1F),
+            4 => self.read_oam(),
+            7 => {
+                let addr = self.v & 0x3FFF;
+                if addr >= 0x3F00 {
+                    (self.read_palette(addr) & 0x3F) | (self.io_latch & 0xC0)
+                } else {
+                    self.read_buffer
+                }
+            },
+            _ => self.io_latch
+        }
+    }
+
+    /**
+     * Writes a register as the CPU does, addr is any mirror in $2000-$3FFF.
+     */
+    pub fn write_register(&mut self, addr:u16, data:u8, mapper: &mut dyn Mapper) {
+        self.io_latch = data;
+        match addr & 0x0007 {
+            0 => {
+                self.ctrl = data;
+                // nametable select goes to t bits 10-11
+                self.t = (self.t & !0x0C00) | ((data as u16 & 0x03) << 10);
+            },
+            1 => self.mask = data,
+            3 => self.oam_addr = data,
+            4 => {
+                self.oam[self.oam_addr as usize] = data;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            },
+            5 => {
+                if !self.w {
+                    self.t = (self.t & !0x001F) | (data as u16 >> 3);
+                    self.x = data & 0x07;
+                } else {
+                    self.t = (self.t & !0x73E0) | ((data as u16 & 0x07) << 12) | ((data as u16 & 0xF8) << 2);
+                }
+                self.w = !self.w;
+            },
+            6 => {
+                if !self.w {
+                    self.t = (self.t & 0x00FF) | ((data as u16 & 0x3F) << 8);
+                } else {
+                    self.t = (self.t & 0xFF00) | data as u16;
+                    self.v = self.t;
+                    mapper.ppu_address(self.v & 0x3FFF, self.cycles);
+                }
+                self.w = !self.w;
+            },
+            7 => {
+                self.write_vram(self.v & 0x3FFF, data, mapper);
+                self.increment_vram_addr(mapper);
+            },
+            // PPUSTATUS is read only
+            _ => {}
+        }
+    }
+
+    /**
+     * Background or sprite rendering is enabled.
+     */
+    pub fn rendering_enabled(&self) -> bool {
+        self.mask & (MaskFlag::ShowBackground.mask() | MaskFlag::ShowSprites.mask()) != 0
+    }
+
+    /**
+     * Palette index of the pixel at x, y in the last rendered frame.
+     */
+    pub fn pixel(&self, x:usize, y:usize) -> u8 {
+        self.frame_buffer[y * SCREEN_WIDTH + x]
+    }
+
+    /**
+     * Advances the PPU by one dot.
+     */
+    pub fn clock(&mut self, mapper: &mut dyn Mapper) {
+        let visible_line = self.scanline < SCREEN_HEIGHT as u16;
+        let pre_render_line = self.scanline == self.pre_render_scanline();
+
+        if self.dot == 1 {
+            if self.scanline == VBLANK_SCANLINE {
+                self.status |= StatusFlag::VerticalBlank.mask();
+                self.frame_complete = true;
+            } else if pre_render_line {
+                self.status &= !(StatusFlag::VerticalBlank.mask()
+                    | StatusFlag::SpriteZeroHit.mask()
+                    | StatusFlag::SpriteOverflow.mask());
+            }
+        }
+
+        let mut skip_dot = false;
+        if self.rendering_enabled() && (visible_line || pre_render_line) {
+            self.render_fetches(mapper, pre_render_line);
+            // the pre-render line is one dot shorter on odd frames
+            skip_dot = pre_render_line && self.dot == 339 && self.frame % 2 == 1 && self.region == Region::NTSC;
+        }
+
+        if visible_line && (1..=256).contains(&self.dot) {
+            self.render_pixel();
+        }
+
+        if skip_dot {
+            self.advance_dot();
+        }
+
+        self.advance_dot();
+        self.cycles += 1;
+    }
+
+    /**
+     * Memory fetches and scroll updates of a rendering scanline.
+     */
+    fn render_fetches(&mut self, mapper: &mut dyn Mapper, pre_render_line:bool) {
+        let dot = self.dot;
+        let background_dot = (2..=257).contains(&dot) || (322..=337).contains(&dot);
+        if background_dot {
+            self.shift_background();
+        }
+
+        if (1..=256).contains(&dot) || (321..=337).contains(&dot) {
+            match dot % 8 {
+                1 => {
+                    if dot >= 9 {
+                        self.reload_background_shifters();
+                    }
+                    self.next_tile = self.read_vram(0x2000 | (self.v & 0x0FFF), mapper);
+                },
+                3 => {
+                    let addr = 0x23C0 | (self.v & 0x0C00) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07);
+                    let shift = ((self.v >> 4) & 0x04) | (self.v & 0x02);
+                    self.next_attribute = (self.read_vram(addr, mapper) >> shift) & 0x03;
+                },
+                5 => self.next_pattern_lo = self.read_vram(self.background_pattern_addr(), mapper),
+                7 => self.next_pattern_hi = self.read_vram(self.background_pattern_addr() + 8, mapper),
+                0 => self.increment_coarse_x(),
+                _ => {}
+            }
+        }
+
+        match dot {
+            256 => self.increment_y(),
+            257 => {
+                self.reload_background_shifters();
+                // copy horizontal scroll bits from t
+                self.v = (self.v & !0x041F) | (self.t & 0x041F);
+                if !pre_render_line {
+                    self.evaluate_sprites();
+                } else {
+                    self.secondary_count = 0;
+                    self.secondary_has_sprite_zero = false;
+                }
+                self.sprite_count = self.secondary_count;
+                self.sprite_zero_on_line = self.secondary_has_sprite_zero;
+            },
+            // copy vertical scroll bits from t
+            280..=304 if pre_render_line => self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0),
+            // unused nametable fetch at the end of the line, the one on dot 337 is done above
+            339 => {
+                self.read_vram(0x2000 | (self.v & 0x0FFF), mapper);
+            },
+            _ => {}
+        }
+
+        if (257..=320).contains(&dot) {
+            self.oam_addr = 0;
+            self.fetch_sprite(mapper);
+        }
+    }
+
+    fn background_pattern_addr(&self) -> u16 {
+        let table = if self.ctrl & ControlFlag::BackgroundTable.mask() != 0 { 0x1000 } else { 0 };
+        table + self.next_tile as u16 * 16 + ((self.v >> 12) & 0x07)
+    }
+
+    fn shift_background(&mut self) {
+        self.pattern_shift_lo <<= 1;
+        self.pattern_shift_hi <<= 1;
+        self.attribute_shift_lo <<= 1;
+        self.attribute_shift_hi <<= 1;
+    }
+
+    fn reload_background_shifters(&mut self) {
+        self.pattern_shift_lo = (self.pattern_shift_lo & 0xFF00) | self.next_pattern_lo as u16;
+        self.pattern_shift_hi = (self.pattern_shift_hi & 0xFF00) | self.next_pattern_hi as u16;
+        let attribute_lo = if self.next_attribute & 0x01 != 0 { 0xFF } else { 0x00 };
+        let attribute_hi = if self.next_attribute & 0x02 != 0 { 0xFF } else { 0x00 };
+        self.attribute_shift_lo = (self.attribute_shift_lo & 0xFF00) | attribute_lo;
+        self.attribute_shift_hi = (self.attribute_shift_hi & 0xFF00) | attribute_hi;
+    }
+
+    /**
+     * Increments coarse X in v, switching horizontal nametable on wrap.
+     */
+    fn increment_coarse_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v &= !0x001F;
+            self.v ^= 0x0400;
+        } else {
+            self.v += 1;
+        }
+    }
+
+    /**
+     * Increments fine Y in v, carrying into coarse Y and switching vertical
+     * nametable after row 29. Rows 30 and 31 wrap without switching.
+     */
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !0x7000;
+        let mut coarse_y = (self.v & 0x03E0) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            self.v ^= 0x0800;
+        } else if coarse_y == 31 {
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !0x03E0) | (coarse_y << 5);
+    }
+
+    /**
+     * Finds the sprites on the current scanline for the next one. After eight
+     * sprites are found the hardware keeps scanning with a bug: the byte index
+     * into each entry is incremented along with the sprite index, so the
+     * overflow flag is set from whatever byte lands in the Y position check.
+     */
+    fn evaluate_sprites(&mut self) {
+        let height = self.sprite_height();
+        let in_range = |y:u8| {
+            let row = self.scanline as i32 - y as i32;
+            row >= 0 && row < height as i32
+        };
+        self.secondary_oam = [0xFF; 32];
+        self.secondary_count = 0;
+        self.secondary_has_sprite_zero = false;
+
+        let mut n = 0;
+        while n < 64 && self.secondary_count < MAX_SPRITES_PER_LINE {
+            if in_range(self.oam[n * 4]) {
+                let index = self.secondary_count * 4;
+                self.secondary_oam[index .. index + 4].copy_from_slice(&self.oam[n * 4 .. n * 4 + 4]);
+                self.secondary_count += 1;
+                if n == 0 {
+                    self.secondary_has_sprite_zero = true;
+                }
+            }
+            n += 1;
+        }
+
+        let mut m = 0;
+        while n < 64 {
+            if in_range(self.oam[n * 4 + m]) {
+                self.status |= StatusFlag::SpriteOverflow.mask();
+                break;
+            }
+            n += 1;
+            m = (m + 1) & 0x03;
+        }
+    }
+
+    fn sprite_height(&self) -> u16 {
+        if self.ctrl & ControlFlag::SpriteSize.mask() != 0 { 16 } else { 8 }
+    }
+
+    /**
+     * Sprite pattern fetches on dots 257-320, two dummy nametable fetches and
+     * two pattern fetches for each of the eight slots. Empty slots fetch tile $FF.
+     */
+    fn fetch_sprite(&mut self, mapper: &mut dyn Mapper) {
+        let slot = ((self.dot - 257) / 8) as usize;
+        let step = (self.dot - 257) % 8;
+        let entry = &self.secondary_oam[slot * 4 .. slot * 4 + 4];
+        let (y, tile, attributes, x) = (entry[0], entry[1], entry[2], entry[3]);
+
+        let height = self.sprite_height();
+        let mut row = (self.scanline.wrapping_sub(y as u16)) & (height - 1);
+        if attributes & 0x80 != 0 {
+            row = height - 1 - row;
+        }
+        let pattern_addr = if height == 16 {
+            let table = (tile as u16 & 0x01) * 0x1000;
+            let tile = (tile & 0xFE) as u16 + (row >> 3);
+            table + tile * 16 + (row & 0x07)
+        } else {
+            let table = if self.ctrl & ControlFlag::SpriteTable.mask() != 0 { 0x1000 } else { 0 };
+            table + tile as u16 * 16 + row
+        };
+
+        match step {
+            0 | 2 => {
+                self.read_vram(0x2000 | (self.v & 0x0FFF), mapper);
+            },
+            4 => {
+                let data = self.read_vram(pattern_addr, mapper);
+                if slot < self.secondary_count {
+                    self.sprites[slot] = SpriteLine {
+                        x,
+                        attributes,
+                        pattern_lo: if attributes & 0x40 != 0 { data.reverse_bits() } else { data },
+                        pattern_hi: 0
+                    };
+                }
+            },
+            6 => {
+                let data = self.read_vram(pattern_addr + 8, mapper);
+                if slot < self.secondary_count {
+                    self.sprites[slot].pattern_hi = if attributes & 0x40 != 0 { data.reverse_bits() } else { data };
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /**
+     * Combines the background and sprite pixels for the current dot.
+     */
+    fn render_pixel(&mut self) {
+        let x = (self.dot - 1) as usize;
+        let y = self.scanline as usize;
+
+        let mut background = 0;
+        let mut background_palette = 0;
+        let show_background = self.mask & MaskFlag::ShowBackground.mask() != 0
+            && (x >= 8 || self.mask & MaskFlag::BackgroundLeft.mask() != 0);
+        if show_background {
+            let bit = 0x8000 >> self.x;
+            background = ((self.pattern_shift_hi & bit != 0) as u8) << 1 | (self.pattern_shift_lo & bit != 0) as u8;
+            background_palette = ((self.attribute_shift_hi & bit != 0) as u8) << 1 | (self.attribute_shift_lo & bit != 0) as u8;
+        }
+
+        let mut sprite = 0;
+        let mut sprite_palette = 0;
+        let mut sprite_behind = false;
+        let show_sprites = self.mask & MaskFlag::ShowSprites.mask() != 0
+            && (x >= 8 || self.mask & MaskFlag::SpritesLeft.mask() != 0);
+        if show_sprites {
+            for (index, line) in self.sprites[..self.sprite_count].iter().enumerate() {
+                let offset = x as i32 - line.x as i32;
+                if !(0..8).contains(&offset) {
+                    continue;
+                }
+                let bit = 7 - offset;
+                let color = ((line.pattern_hi >> bit) & 0x01) << 1 | ((line.pattern_lo >> bit) & 0x01);
+                if color == 0 {
+                    continue;
+                }
+                if index == 0 && self.sprite_zero_on_line && background != 0 && x != 255 {
+                    self.status |= StatusFlag::SpriteZeroHit.mask();
+                }
+                sprite = color;
+                sprite_palette = line.attributes & 0x03;
+                sprite_behind = line.attributes & 0x20 != 0;
+                break;
+            }
+        }
+
+        let palette_addr = if sprite != 0 && (background == 0 || !sprite_behind) {
+            0x3F10 | (sprite_palette as u16) << 2 | sprite as u16
+        } else if background != 0 {
+            0x3F00 | (background_palette as u16) << 2 | background as u16
+        } else if !self.rendering_enabled() && self.v & 0x3F00 == 0x3F00 {
+            // with rendering off and v pointing at the palette, that color is shown
+            self.v & 0x3FFF
+        } else {
+            0x3F00
+        };
+        self.frame_buffer[y * SCREEN_WIDTH + x] = self.read_palette(palette_addr) & 0x3F;
+    }
+
+    /**
+     * Moves to the next dot, wrapping to the next scanline and frame.
+     */
+    fn advance_dot(&mut self) {
+        self.dot += 1;
+        if self.dot == DOTS_PER_SCANLINE {
+            self.dot = 0;
+            self.scanline += 1;
+            if self.scanline == self.region.scanlines_per_frame() {
+                self.scanline = 0;
+                self.frame += 1;
+            }
+        }
+    }
+
+    fn pre_render_scanline(&self) -> u16 {
+        self.region.scanlines_per_frame() - 1
+    }
+
+    fn read_oam(&self) -> u8 {
+        let data = self.oam[self.oam_addr as usize];
+        // bits 2-4 of the sprite attribute byte do not exist
+        if self.oam_addr & 0x03 == 0x02 { data & 0xE3 } else { data }
+    }
+
+    fn increment_vram_addr(&mut self, mapper: &mut dyn Mapper) {
+        let rendering_line = self.scanline < SCREEN_HEIGHT as u16 || self.scanline == self.pre_render_scanline();
+        if self.rendering_enabled() && rendering_line {
+            // during rendering PPUDATA accesses bump both scroll counters instead
+            self.increment_coarse_x();
+            self.increment_y();
+        } else {
+            let step = if self.ctrl & ControlFlag::VramIncrement.mask() != 0 { 32 } else { 1 };
+            self.v = self.v.wrapping_add(step) & 0x7FFF;
+        }
+        mapper.ppu_address(self.v & 0x3FFF, self.cycles);
+    }
+
+    /**
+     * Reads PPU memory, addr is in $0000-$3FFF.
+     */
+    pub fn read_vram(&mut self, addr:u16, mapper: &mut dyn Mapper) -> u8 {
+        mapper.ppu_address(addr, self.cycles);
+        match addr {
+            0x0000..=0x1FFF => mapper.ppu_read(addr),
+            0x2000..=0x3EFF => self.vram[nametable_index(addr, mapper.mirroring())],
+            _ => self.read_palette(addr)
+        }
+    }
+
+    /**
+     * Writes PPU memory, addr is in $0000-$3FFF.
+     */
+    pub fn write_vram(&mut self, addr:u16, data:u8, mapper: &mut dyn Mapper) {
+        mapper.ppu_address(addr, self.cycles);
+        match addr {
+            0x0000..=0x1FFF => mapper.ppu_write(addr, data),
+            0x2000..=0x3EFF => self.vram[nametable_index(addr, mapper.mirroring())] = data,
+            _ => self.palette[palette_index(addr)] = data & 0x3F
+        }
+    }
+
+    fn read_palette(&self, addr:u16) -> u8 {
+        let data = self.palette[palette_index(addr)];
+        if self.mask & MaskFlag::Grayscale.mask() != 0 { data & 0x30 } else { data }
+    }
+}
+
+impl Default for PPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+ * Index into nametable RAM for an address in $2000-$3EFF.
+ */
+fn nametable_index(addr:u16, mirroring:Mirroring) -> usize {
+    let addr = addr as usize & 0x0FFF;
+    let offset = addr & 0x03FF;
+    match mirroring {
+        Mirroring::Horizontal => ((addr & 0x0800) >> 1) | offset,
+        Mirroring::Vertical => addr & 0x07FF,
+        Mirroring::SingleScreenLower => offset,
+        Mirroring::SingleScreenUpper => 0x0400 | offset,
+        Mirroring::FourScreen => addr
+    }
+}
+
+/**
+ * Index into palette RAM, the sprite backdrop entries mirror the background ones.
+ */
+fn palette_index(addr:u16) -> usize {
+    let index = addr as usize & 0x1F;
+    if index & 0x13 == 0x10 { index & 0x0F } else { index }
+}
@@ -0,0 +1,269 @@
+use nesguin::apu::{mix, APU, NTSC_CPU_CLOCK};
+use nesguin::apu::resampler::Resampler;
+use nesguin::bus::NesBus;
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::CPU;
+
+/***
+ * Helper method for clocking the APU a number of CPU cycles.
+ */
+fn run_cycles(apu: &mut APU, cycles:u32) {
+    for _ in 0..cycles {
+        apu.clock();
+    }
+}
+
+#[test]
+fn test_length_counter_status() {
+    let mut apu = APU::new();
+    // length loads are ignored while the channel is disabled
+    apu.write_register(0x4003, 0x08);
+    assert_eq!(apu.peek_status(), 0x00);
+    apu.write_register(0x4015, 0x0F);
+    apu.write_register(0x4003, 0x08);
+    apu.write_register(0x400B, 0x08);
+    apu.write_register(0x400F, 0x08);
+    assert_eq!(apu.peek_status(), 0b0000_1101);
+    apu.write_register(0x4015, 0x0E);
+    assert_eq!(apu.peek_status(), 0b0000_1100);
+}
+
+#[test]
+fn test_length_counter_half_frames() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0x01);
+    // length index 3 loads 2 half frames
+    apu.write_register(0x4003, 0x18);
+    run_cycles(&mut apu, 14914);
+    assert_eq!(apu.pulse1.length.counter, 1);
+    run_cycles(&mut apu, 29830 - 14914);
+    assert_eq!(apu.pulse1.length.counter, 0);
+    assert_eq!(apu.peek_status() & 0x01, 0);
+}
+
+#[test]
+fn test_frame_irq() {
+    let mut apu = APU::new();
+    run_cycles(&mut apu, 29827);
+    assert!(!apu.irq());
+    run_cycles(&mut apu, 1);
+    assert!(apu.irq());
+    assert_eq!(apu.read_status(), 0x40);
+    assert!(!apu.irq());
+    assert_eq!(apu.peek_status(), 0x00);
+
+    // the inhibit flag clears and suppresses the IRQ
+    run_cycles(&mut apu, 29830);
+    assert!(apu.irq());
+    apu.write_register(0x4017, 0x40);
+    assert!(!apu.irq());
+    run_cycles(&mut apu, 29830 * 2);
+    assert!(!apu.irq());
+}
+
+#[test]
+fn test_five_step_mode() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0x01);
+    apu.write_register(0x4003, 0x18);
+    // switching to 5-step mode clocks a half frame right after the write
+    apu.write_register(0x4017, 0x80);
+    run_cycles(&mut apu, 5);
+    assert_eq!(apu.pulse1.length.counter, 1);
+    run_cycles(&mut apu, 37282 * 2);
+    assert!(!apu.irq());
+}
+
+#[test]
+fn test_pulse_sweep_negate() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0x03);
+    for base in [0x4000, 0x4004] {
+        apu.write_register(base, 0x3F);
+        // enabled, period 0, negate, shift 1
+        apu.write_register(base + 1, 0x89);
+        apu.write_register(base + 2, 0x00);
+        apu.write_register(base + 3, 0x01);
+    }
+    run_cycles(&mut apu, 14914);
+    // pulse 1 subtracts one more than pulse 2
+    assert_eq!(apu.pulse1.timer_period, 0x0100 - 0x80 - 1);
+    assert_eq!(apu.pulse2.timer_period, 0x0100 - 0x80);
+}
+
+#[test]
+fn test_pulse_sweep_mutes_channel() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0x01);
+    // constant volume 15, 50% duty
+    apu.write_register(0x4000, 0xBF);
+    // period $500 with sweep shift 3 targets $5A0
+    apu.write_register(0x4001, 0x03);
+    apu.write_register(0x4002, 0x00);
+    apu.write_register(0x4003, 0x05);
+    let levels: Vec<u8> = (0..0x2000).map(|_| { apu.clock(); apu.pulse1.output() }).collect();
+    assert!(levels.contains(&15) && levels.contains(&0));
+    // the sweep target overflows $7FF even with the sweep disabled
+    apu.write_register(0x4001, 0x00);
+    let levels: Vec<u8> = (0..0x2000).map(|_| { apu.clock(); apu.pulse1.output() }).collect();
+    assert!(levels.iter().all(|&level| level == 0));
+}
+
+#[test]
+fn test_triangle_ultrasonic() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0x04);
+    apu.write_register(0x4008, 0xFF);
+    apu.write_register(0x400A, 0x00);
+    apu.write_register(0x400B, 0x08);
+    run_cycles(&mut apu, 7458);
+    let level = apu.triangle.output();
+    run_cycles(&mut apu, 100);
+    assert_eq!(apu.triangle.output(), level);
+
+    apu.triangle.silence_ultrasonic = false;
+    let levels: Vec<u8> = (0..32).map(|_| { apu.clock(); apu.triangle.output() }).collect();
+    assert_eq!(*levels.iter().max().unwrap(), 15);
+    assert_eq!(*levels.iter().min().unwrap(), 0);
+}
+
+#[test]
+fn test_noise_modes() {
+    let mut apu = APU::new();
+    apu.write_register(0x4015, 0x08);
+    apu.write_register(0x400C, 0x3F);
+    apu.write_register(0x400F, 0x08);
+    let mut sequence = |mode:u8| -> Vec<u8> {
+        apu.write_register(0x400E, mode);
+        (0..93 * 4 * 2).map(|_| { apu.clock(); apu.noise.output() }).step_by(4).collect()
+    };
+    let long = sequence(0x00);
+    let short = sequence(0x80);
+    // the short mode sequence repeats every 93 steps
+    assert_eq!(short[..93], short[93..]);
+    assert_ne!(long[..93], long[93..]);
+}
+
+#[test]
+fn test_mixer() {
+    assert_eq!(mix(0, 0, 0, 0, 0), 0.0);
+    assert!((mix(15, 15, 0, 0, 0) - 0.2585).abs() < 0.001);
+    assert!((mix(0, 0, 15, 15, 127) - 0.7415).abs() < 0.001);
+    // the mixer is non-linear, two pulses are quieter than twice one
+    assert!(mix(15, 15, 0, 0, 0) < 2.0 * mix(15, 0, 0, 0, 0));
+}
+
+#[test]
+fn test_frame_irq_reaches_cpu() {
+    // CLI, JMP $8001
+    let mut cpu = CPU::with_bus(NesBus::new());
+    cpu.load_program(vec![0x58, 0x4c, 0x01, 0x80]);
+    cpu.reset();
+    cpu.memory.write_word(0xFFFE, 0x9000);
+    while cpu.cycles < 29900 {
+        cpu.step();
+    }
+    assert!(cpu.program_counter >= 0x9000);
+    assert_eq!(cpu.memory.read(0x4015) & 0x40, 0x40);
+    assert!(!cpu.memory.irq());
+}
+
+#[test]
+fn test_dmc_direct_load_and_playback() {
+    let mut apu = APU::new();
+    apu.write_register(0x4011, 0xC0);
+    assert_eq!(apu.dmc.output(), 0x40);
+    // fastest rate, 17 byte sample at $C040
+    apu.write_register(0x4010, 0x0F);
+    apu.write_register(0x4012, 0x01);
+    apu.write_register(0x4013, 0x01);
+    apu.write_register(0x4015, 0x10);
+    assert_eq!(apu.dmc.dma_request(), Some(0xC040));
+    apu.dmc.load_sample(0xFF);
+    assert_eq!(apu.dmc.dma_request(), None);
+    // the first byte starts playing once the silent output cycle ends
+    run_cycles(&mut apu, 54 * 8);
+    assert_eq!(apu.dmc.dma_request(), Some(0xC041));
+    run_cycles(&mut apu, 54 * 8);
+    assert_eq!(apu.dmc.output(), 0x40 + 16);
+}
+
+#[test]
+fn test_dmc_irq_and_loop() {
+    let mut apu = APU::new();
+    apu.write_register(0x4010, 0x80);
+    apu.write_register(0x4013, 0x00);
+    apu.write_register(0x4015, 0x10);
+    assert_eq!(apu.peek_status(), 0x10);
+    apu.dmc.load_sample(0x00);
+    assert!(apu.irq());
+    assert_eq!(apu.peek_status(), 0x80);
+    // writing $4015 acknowledges the DMC IRQ
+    apu.write_register(0x4015, 0x00);
+    assert!(!apu.irq());
+
+    apu.write_register(0x4010, 0x40);
+    apu.write_register(0x4015, 0x10);
+    apu.dmc.load_sample(0x00);
+    assert!(!apu.irq());
+    assert_eq!(apu.peek_status(), 0x10);
+    assert_eq!(apu.dmc.bytes_remaining, 1);
+}
+
+#[test]
+fn test_resampler_sample_count() {
+    let mut resampler = Resampler::new(NTSC_CPU_CLOCK, 48000.0);
+    for _ in 0..60 {
+        resampler.end_frame(29781);
+    }
+    let expected = 60.0 * 29781.0 * 48000.0 / NTSC_CPU_CLOCK;
+    assert!((resampler.samples().len() as f64 - expected).abs() <= 1.0);
+
+    // dynamic rate control stretches the output, within limits
+    resampler.take_samples();
+    resampler.set_rate_adjustment(0.005);
+    resampler.end_frame(NTSC_CPU_CLOCK as u32);
+    assert!((resampler.take_samples().len() as f64 - 48240.0).abs() <= 1.0);
+    resampler.set_rate_adjustment(0.5);
+    resampler.end_frame(NTSC_CPU_CLOCK as u32);
+    assert!((resampler.take_samples().len() as f64 - 48480.0).abs() <= 1.0);
+}
+
+#[test]
+fn test_resampler_filters() {
+    let mut resampler = Resampler::new(NTSC_CPU_CLOCK, 44100.0);
+    // a constant level decays through the high-pass filters
+    resampler.add_delta(0, 0.5);
+    resampler.end_frame(NTSC_CPU_CLOCK as u32 / 2);
+    let samples = resampler.take_samples();
+    assert!(samples.iter().any(|&sample| sample > 0.3));
+    assert!(samples.last().unwrap().abs() < 0.001);
+
+    // a 30 kHz square wave is above Nyquist and mostly removed instead of aliasing
+    let half_period = (NTSC_CPU_CLOCK / 60000.0) as u32;
+    let mut level = 0.5;
+    for step in 0..2000 {
+        resampler.add_delta(step * half_period, level);
+        level = -level;
+    }
+    resampler.end_frame(2000 * half_period);
+    let samples = resampler.take_samples();
+    let rms = (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt();
+    assert!(rms < 0.05, "rms {}", rms);
+}
+
+#[test]
+fn test_apu_audio_frame() {
+    let mut apu = APU::new();
+    apu.resampler.set_sample_rate(48000.0);
+    apu.write_register(0x4015, 0x01);
+    apu.write_register(0x4000, 0xBF);
+    apu.write_register(0x4002, 0xFD);
+    apu.write_register(0x4003, 0x00);
+    run_cycles(&mut apu, 29781);
+    apu.end_audio_frame();
+    let samples = apu.resampler.take_samples_i16();
+    assert_eq!(samples.len(), 798);
+    assert!(samples.iter().any(|&sample| sample > 1000));
+    assert!(samples.iter().any(|&sample| sample < -1000));
+}
@@ -0,0 +1,119 @@
+use nesguin::bus::NesBus;
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::CPU;
+use nesguin::emu6502::ram::RAM;
+
+#[test]
+fn test_ram_bus_covers_full_address_space() {
+    let mut memory = RAM::new();
+    memory.write(0xFFFF, 0x42);
+    assert_eq!(memory.read(0xFFFF), 0x42);
+    assert_eq!(memory.peek(0xFFFF), 0x42);
+    // word access wraps around the address space
+    memory.write(0x0000, 0x12);
+    assert_eq!(memory.read_word(0xFFFF), 0x1242);
+}
+
+#[test]
+fn test_nes_bus_ram_mirroring() {
+    let mut bus = NesBus::new();
+    bus.write(0x0001, 0x11);
+    assert_eq!(bus.read(0x0801), 0x11);
+    assert_eq!(bus.read(0x1001), 0x11);
+    assert_eq!(bus.read(0x1801), 0x11);
+    bus.write(0x1FFF, 0x22);
+    assert_eq!(bus.peek(0x07FF), 0x22);
+}
+
+#[test]
+fn test_nes_bus_open_bus() {
+    let mut bus = NesBus::new();
+    bus.write(0x0000, 0x5A);
+    bus.read(0x0000);
+    // nothing drives the bus at the CPU test mode registers
+    assert_eq!(bus.read(0x4018), 0x5A);
+    assert_eq!(bus.read(0x401F), 0x5A);
+    bus.write(0x0001, 0x80);
+    assert_eq!(bus.read(0x401A), 0x80);
+}
+
+#[test]
+fn test_nes_bus_peek_has_no_side_effects() {
+    let mut bus = NesBus::new();
+    bus.write(0x0000, 0x01);
+    bus.write(0x0001, 0x02);
+    bus.read(0x0000);
+    assert_eq!(bus.peek(0x0001), 0x02);
+    assert_eq!(bus.open_bus(), 0x01);
+}
+
+#[test]
+fn test_cpu_on_nes_bus() {
+    let mut cpu = CPU::with_bus(NesBus::new());
+    // LDA #$c0, STA $0810, TAX, INX, BRK
+    cpu.load_program(vec![0xa9, 0xc0, 0x8d, 0x10, 0x08, 0xaa, 0xe8, 0x00]);
+    cpu.reset();
+    cpu.run();
+    assert_eq!(cpu.register_x, 0xc1);
+    assert_eq!(cpu.memory.ram[0x0010], 0xc0);
+    assert_eq!(cpu.memory.peek_word(0xFFFC), 0x8000);
+}
+
+/***
+ * Flat RAM that records the number of ticked cycles at every access.
+ */
+struct CycleLogBus {
+    ram:RAM,
+    cycles:u64,
+    accesses:Vec<(u16, u64)>
+}
+
+impl Bus for CycleLogBus {
+    fn read(&mut self, addr:u16) -> u8 {
+        self.accesses.push((addr, self.cycles));
+        self.ram.read(addr)
+    }
+
+    fn write(&mut self, addr:u16, data:u8) {
+        self.accesses.push((addr, self.cycles));
+        self.ram.write(addr, data);
+    }
+
+    fn peek(&self, addr:u16) -> u8 {
+        self.ram.peek(addr)
+    }
+
+    fn tick(&mut self, cycles:u8) {
+        self.cycles += cycles as u64;
+    }
+}
+
+#[test]
+fn test_bus_is_clocked_per_access() {
+    let mut cpu = CPU::with_bus(CycleLogBus { ram: RAM::new(), cycles: 0, accesses: Vec::new() });
+    // STA $0200, PHA
+    cpu.load_program(vec![0x8d, 0x00, 0x02, 0x48]);
+    cpu.reset();
+    let start = cpu.memory.cycles;
+    cpu.memory.accesses.clear();
+    cpu.step();
+    cpu.step();
+    let accesses:Vec<(u16, u64)> = cpu.memory.accesses.iter().map(|(addr, cycles)| (*addr, cycles - start)).collect();
+    // every access is clocked by its own cycle, cycles without an access follow the instruction
+    assert_eq!(accesses, vec![(0x8000, 1), (0x8001, 2), (0x8002, 3), (0x0200, 4), (0x8003, 5), (0x01FD, 6)]);
+    assert_eq!(cpu.memory.cycles - start, 7);
+    assert_eq!(cpu.cycles, cpu.memory.cycles);
+}
+
+#[test]
+fn test_ppu_status_polled_mid_instruction() {
+    let mut cpu = CPU::with_bus(NesBus::new());
+    // LDA $2002
+    cpu.load_program(vec![0xad, 0x02, 0x20]);
+    cpu.reset();
+    // vblank starts about ten dots from now, the read happens twelve dots in
+    cpu.memory.ppu.scanline = 240;
+    cpu.memory.ppu.dot = 332;
+    cpu.step();
+    assert_eq!(cpu.register_a & 0x80, 0x80);
+}
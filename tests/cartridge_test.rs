@@ -0,0 +1,108 @@
+use nesguin::cartridge::{Cartridge, CartridgeError, ConsoleType, Mirroring, RomFormat, Timing};
+
+/***
+ * Helper method for building a ROM image from a header and filled PRG/CHR banks.
+ */
+fn build_rom(header: [u8; 16], trainer: bool, prg_size: usize, chr_size: usize) -> Vec<u8> {
+    let mut rom = header.to_vec();
+    if trainer {
+        rom.extend(vec![0x77; 512]);
+    }
+    rom.extend((0..prg_size).map(|i| i as u8));
+    rom.extend(vec![0xCC; chr_size]);
+    rom
+}
+
+#[test]
+fn test_ines_header() {
+    // 2 PRG banks, 1 CHR bank, mapper 0x12, vertical mirroring, battery, trainer
+    let header = [b'N', b'E', b'S', 0x1A, 2, 1, 0x27, 0x10, 0, 1, 0, 0, 0, 0, 0, 0];
+    let cartridge = Cartridge::from_bytes(&build_rom(header, true, 0x8000, 0x2000)).unwrap();
+    assert_eq!(cartridge.format, RomFormat::INes);
+    assert_eq!(cartridge.mapper, 0x12);
+    assert_eq!(cartridge.mirroring, Mirroring::Vertical);
+    assert!(cartridge.battery);
+    assert_eq!(cartridge.trainer.as_ref().map(|t| t.len()), Some(512));
+    assert_eq!(cartridge.prg_rom.len(), 0x8000);
+    assert_eq!(cartridge.prg_rom[0x1234], 0x34);
+    assert_eq!(cartridge.chr_rom.len(), 0x2000);
+    assert!(!cartridge.has_chr_ram());
+    assert_eq!(cartridge.prg_ram_size, 0x2000);
+    assert_eq!(cartridge.timing, Timing::PAL);
+    assert_eq!(cartridge.console_type, ConsoleType::NES);
+}
+
+#[test]
+fn test_ines_chr_ram_and_dirty_header() {
+    // "DiskDude!" in the tail, upper mapper nibble must be ignored
+    let mut header = [b'N', b'E', b'S', 0x1A, 1, 0, 0x18, b'D', b'i', b's', b'k', b'D', b'u', b'd', b'e', b'!'];
+    let cartridge = Cartridge::from_bytes(&build_rom(header, false, 0x4000, 0)).unwrap();
+    assert_eq!(cartridge.mapper, 1);
+    assert_eq!(cartridge.mirroring, Mirroring::FourScreen);
+    assert!(cartridge.has_chr_ram());
+    assert_eq!(cartridge.chr_ram_size, 0x2000);
+    assert_eq!(cartridge.timing, Timing::NTSC);
+
+    header[7..].fill(0);
+    header[7] = 0x01;
+    let cartridge = Cartridge::from_bytes(&build_rom(header, false, 0x4000, 0)).unwrap();
+    assert_eq!(cartridge.console_type, ConsoleType::VsSystem);
+}
+
+#[test]
+fn test_nes20_header() {
+    // mapper 0x104 submapper 3, PRG RAM 8K, PRG NVRAM 32K, CHR RAM 8K, Dendy
+    let header = [b'N', b'E', b'S', 0x1A, 0x02, 0x00, 0x42, 0x08, 0x31, 0x00, 0x97, 0x07, 0x03, 0x00, 0, 0];
+    let cartridge = Cartridge::from_bytes(&build_rom(header, false, 0x8000, 0)).unwrap();
+    assert_eq!(cartridge.format, RomFormat::Nes20);
+    assert_eq!(cartridge.mapper, 0x104);
+    assert_eq!(cartridge.submapper, 3);
+    assert!(cartridge.battery);
+    assert_eq!(cartridge.prg_ram_size, 0x2000);
+    assert_eq!(cartridge.prg_nvram_size, 0x8000);
+    assert_eq!(cartridge.chr_ram_size, 0x2000);
+    assert_eq!(cartridge.chr_nvram_size, 0);
+    assert_eq!(cartridge.timing, Timing::Dendy);
+
+    // exponent-multiplier PRG size: 2^14 * 3 bytes, extended console type
+    let header = [b'N', b'E', b'S', 0x1A, (14 << 2) | 1, 0x00, 0x00, 0x0B, 0x00, 0x0F, 0, 0, 0x02, 0x04, 0, 0];
+    let cartridge = Cartridge::from_bytes(&build_rom(header, false, 0xC000, 0)).unwrap();
+    assert_eq!(cartridge.prg_rom.len(), 0xC000);
+    assert_eq!(cartridge.timing, Timing::MultiRegion);
+    assert_eq!(cartridge.console_type, ConsoleType::Extended(4));
+}
+
+#[test]
+fn test_malformed_files() {
+    let header = [b'N', b'E', b'S', 0x1A, 2, 1, 0x04, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    assert!(matches!(Cartridge::from_bytes(&header[..10]), Err(CartridgeError::TruncatedHeader { len: 10 })));
+
+    let mut bad_magic = header;
+    bad_magic[3] = 0x1B;
+    assert!(matches!(Cartridge::from_bytes(&bad_magic), Err(CartridgeError::InvalidMagic(_))));
+
+    let mut no_prg = header;
+    no_prg[4] = 0;
+    assert!(matches!(Cartridge::from_bytes(&build_rom(no_prg, true, 0, 0)), Err(CartridgeError::MissingPrgRom)));
+
+    let mut rom = header.to_vec();
+    rom.extend(vec![0; 100]);
+    assert!(matches!(Cartridge::from_bytes(&rom),
+        Err(CartridgeError::TruncatedTrainer { expected: 512, available: 100 })));
+
+    let rom = build_rom(header, true, 0x7000, 0);
+    assert!(matches!(Cartridge::from_bytes(&rom),
+        Err(CartridgeError::TruncatedPrgRom { expected: 0x8000, available: 0x7000 })));
+
+    let rom = build_rom(header, true, 0x8000, 0x1000);
+    let err = Cartridge::from_bytes(&rom).unwrap_err();
+    assert!(matches!(err, CartridgeError::TruncatedChrRom { expected: 0x2000, available: 0x1000 }));
+    assert_eq!(err.to_string(), "CHR ROM truncated: expected 8192 bytes, 4096 available");
+
+    // 2^63 * 7 bytes does not fit
+    let huge = [b'N', b'E', b'S', 0x1A, 0xFF, 0, 0, 0x08, 0, 0x0F, 0, 0, 0, 0, 0, 0];
+    assert!(matches!(Cartridge::from_bytes(&huge), Err(CartridgeError::RomSizeOverflow)));
+
+    assert!(matches!(Cartridge::load("does/not/exist.nes"), Err(CartridgeError::Io(_))));
+}
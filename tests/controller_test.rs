@@ -0,0 +1,124 @@
+use nesguin::bus::NesBus;
+use nesguin::controller::{Button, Controller, StandardController};
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::CPU;
+
+/***
+ * Helper method for strobing the controllers through the bus.
+ */
+fn strobe(bus: &mut NesBus) {
+    bus.write(0x4016, 0x01);
+    bus.write(0x4016, 0x00);
+}
+
+#[test]
+fn test_standard_controller_serial_read() {
+    let mut controller = StandardController::new();
+    controller.set_button(Button::A, true);
+    controller.set_button(Button::Start, true);
+    controller.set_button(Button::Right, true);
+    controller.write_strobe(true);
+    controller.write_strobe(false);
+    let bits: Vec<u8> = (0..8).map(|_| controller.read()).collect();
+    assert_eq!(bits, vec![1, 0, 0, 1, 0, 0, 0, 1]);
+    // an official controller returns 1 after the eighth read
+    assert_eq!(controller.read(), 1);
+    assert_eq!(controller.read(), 1);
+}
+
+#[test]
+fn test_standard_controller_strobe_high() {
+    let mut controller = StandardController::new();
+    controller.write_strobe(true);
+    assert_eq!(controller.read(), 0);
+    // while strobe is high the A button is read continuously
+    controller.set_button(Button::A, true);
+    assert_eq!(controller.read(), 1);
+    assert_eq!(controller.read(), 1);
+    // releasing buttons after the strobe falls does not change the latched state
+    controller.write_strobe(false);
+    controller.set_button(Button::A, false);
+    assert_eq!(controller.peek(), 1);
+    assert_eq!(controller.read(), 1);
+    assert_eq!(controller.read(), 0);
+}
+
+#[test]
+fn test_controller_ports() {
+    let mut bus = NesBus::new();
+    bus.set_button(0, Button::B, true);
+    bus.set_button(1, Button::A, true);
+    strobe(&mut bus);
+    bus.write(0x0000, 0x40);
+    bus.read(0x0000);
+    // upper bits are open bus
+    assert_eq!(bus.read(0x4016), 0x40);
+    assert_eq!(bus.read(0x4016) & 0x1F, 0x01);
+    bus.write(0x0000, 0x40);
+    bus.read(0x0000);
+    assert_eq!(bus.peek(0x4017), 0x41);
+    assert_eq!(bus.read(0x4017), 0x41);
+    assert_eq!(bus.read(0x4017) & 0x1F, 0x00);
+
+    // an empty port drives nothing
+    bus.connect_controller(1, None);
+    bus.write(0x0000, 0x00);
+    bus.read(0x0000);
+    assert_eq!(bus.read(0x4017), 0x00);
+}
+
+#[test]
+fn test_custom_controller() {
+    /***
+     * Device that reports a light sensor on D3 and the trigger on D4, like the Zapper.
+     */
+    struct LightGun {
+        trigger:bool
+    }
+
+    impl Controller for LightGun {
+        fn write_strobe(&mut self, _strobe:bool) {}
+
+        fn read(&mut self) -> u8 {
+            self.peek()
+        }
+
+        fn peek(&self) -> u8 {
+            0x08 | if self.trigger { 0x10 } else { 0x00 }
+        }
+    }
+
+    let mut bus = NesBus::new();
+    bus.connect_controller(1, Some(Box::new(LightGun { trigger: true })));
+    bus.set_button(1, Button::A, true);
+    bus.write(0x0000, 0x00);
+    bus.read(0x0000);
+    assert_eq!(bus.read(0x4017), 0x18);
+}
+
+#[test]
+fn test_controller_read_by_cpu() {
+    // LDA #$01, STA $4016, LSR A, STA $4016, LDA $4016
+    let mut cpu = CPU::with_bus(NesBus::new());
+    cpu.load_program(vec![0xa9, 0x01, 0x8d, 0x16, 0x40, 0x4a, 0x8d, 0x16, 0x40, 0xad, 0x16, 0x40]);
+    cpu.reset();
+    cpu.memory.set_button(0, Button::A, true);
+    for _ in 0..5 {
+        cpu.step();
+    }
+    // the high byte of the operand is left on the data bus
+    assert_eq!(cpu.register_a, 0x41);
+}
+
+#[test]
+fn test_dmc_dma_corrupts_controller_read() {
+    let mut bus = NesBus::new();
+    bus.set_button(0, Button::B, true);
+    bus.write(0x4016, 0x01);
+    bus.write(0x4016, 0x00);
+    // a sample fetch halts the CPU on its controller read
+    bus.write(0x4015, 0x10);
+    assert!(matches!(bus.halt(0x4016), 3 | 4));
+    // the halted read clocked out A, the CPU reads B
+    assert_eq!(bus.read(0x4016) & 0x01, 1);
+}
@@ -1,6 +1,8 @@
+#![allow(clippy::needless_return)]
+
 use std::vec;
 
-use nesguin::emu6502::cpu::{CPU, CPUFlag};
+use nesguin::emu6502::cpu::{CPU, CPUFlag, CPUVariant, StepResult, StopCondition, StopReason};
 
 /***
  * Helper method for creating a CPU instance with program.
@@ -44,7 +46,33 @@ fn test_flags() {
 
 #[test]
 fn test_addressing_modes() {
-    // TODO: To be implemented
+    // LDX #$02, LDY #$03, then LDA with each addressing mode, STA to $0300+n
+    let program = vec![
+        0xa2, 0x02, 0xa0, 0x03,
+        0xa5, 0x10, 0x8d, 0x00, 0x03,       // LDA $10
+        0xb5, 0xff, 0x8d, 0x01, 0x03,       // LDA $FF,X wraps to $01
+        0xad, 0x00, 0x02, 0x8d, 0x02, 0x03, // LDA $0200
+        0xbd, 0xfe, 0x01, 0x8d, 0x03, 0x03, // LDA $01FE,X
+        0xb9, 0xfd, 0x01, 0x8d, 0x04, 0x03, // LDA $01FD,Y
+        0xa1, 0xfe, 0x8d, 0x05, 0x03,       // LDA ($FE,X), pointer at $00
+        0xb1, 0xff, 0x8d, 0x06, 0x03,       // LDA ($FF),Y, pointer high byte wraps to $00
+    ];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.memory.write_byte(0x0010, 0x11);
+    // $00-$01 hold the pointer $2200
+    cpu.memory.write_word(0x0000, 0x2200);
+    cpu.memory.write_byte(0x0200, 0x33);
+    cpu.memory.write_byte(0x2200, 0x44);
+    cpu.memory.write_byte(0x00ff, 0x01);
+    cpu.memory.write_byte(0x0004, 0x55);
+    cpu.run();
+    assert_eq!(cpu.memory.read_byte(0x0300), 0x11);
+    assert_eq!(cpu.memory.read_byte(0x0301), 0x22);
+    assert_eq!(cpu.memory.read_byte(0x0302), 0x33);
+    assert_eq!(cpu.memory.read_byte(0x0303), 0x33);
+    assert_eq!(cpu.memory.read_byte(0x0304), 0x33);
+    assert_eq!(cpu.memory.read_byte(0x0305), 0x44);
+    assert_eq!(cpu.memory.read_byte(0x0306), 0x55);
 }
 
 #[test] 
@@ -80,7 +108,7 @@ fn test_op_tsx() {
     // set stack pointer
     cpu.stack_pointer = 19;
     // run program
-    cpu.run();
+    cpu.run_until(StopCondition::Opcode(0x00));
     // x should have the same value with stack pointer
     assert_eq!(cpu.register_x, cpu.stack_pointer);
 }
@@ -92,7 +120,7 @@ fn test_op_pha() {
     let program = vec![0xa9, 0x01, 0x48];
     let mut cpu = load_test_program_to_cpu(program);
     // run the program
-    cpu.run();
+    cpu.run_until(StopCondition::Opcode(0x00));
     // stack should have the value 0x01
     let stack_address = cpu.resolve_stack_addr(cpu.stack_pointer + 1);
     let value = cpu.memory.read_byte(stack_address);
@@ -119,7 +147,7 @@ fn test_op_php() {
     let program = vec![0xa9, 0x21, 0x48, 0x28];
     // create cpu
     let mut cpu = load_test_program_to_cpu(program);
-    cpu.run();
+    cpu.run_until(StopCondition::Opcode(0x00));
     assert_eq!(cpu.status, 0x21);
 }
 
@@ -159,4 +187,343 @@ fn test_op_tya() {
     cpu.run();
     assert_eq!(cpu.register_a, 88);
     assert_eq!(cpu.register_y, 88);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_op_adc() {
+    // LDA #$50, ADC #$50 -> signed overflow, no carry
+    let program = vec![0xa9, 0x50, 0x69, 0x50];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0xa0);
+    assert!(cpu.get_cpu_flag(CPUFlag::Overflow));
+    assert!(!cpu.get_cpu_flag(CPUFlag::Carry));
+    assert!(cpu.get_cpu_flag(CPUFlag::Negative));
+
+    // SEC, LDA #$ff, ADC #$00 -> carry out, zero result
+    let program = vec![0x38, 0xa9, 0xff, 0x69, 0x00];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x00);
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+    assert!(cpu.get_cpu_flag(CPUFlag::Zero));
+    assert!(!cpu.get_cpu_flag(CPUFlag::Overflow));
+}
+
+#[test]
+fn test_op_sbc() {
+    // SEC, LDA #$50, SBC #$b0 -> signed overflow, borrow
+    let program = vec![0x38, 0xa9, 0x50, 0xe9, 0xb0];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0xa0);
+    assert!(cpu.get_cpu_flag(CPUFlag::Overflow));
+    assert!(!cpu.get_cpu_flag(CPUFlag::Carry));
+
+    // CLC, LDA #$05, SBC #$02 -> borrow in subtracts one more
+    let program = vec![0x18, 0xa9, 0x05, 0xe9, 0x02];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x02);
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+}
+
+#[test]
+fn test_logical_ops() {
+    // LDA #$f0, AND #$3c, ORA #$01, EOR #$ff
+    let program = vec![0xa9, 0xf0, 0x29, 0x3c, 0x09, 0x01, 0x49, 0xff];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0xce);
+    assert!(cpu.get_cpu_flag(CPUFlag::Negative));
+}
+
+#[test]
+fn test_shifts_and_rotates() {
+    // LDA #$81, ASL A, ROL A
+    let program = vec![0xa9, 0x81, 0x0a, 0x2a];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x05);
+    assert!(!cpu.get_cpu_flag(CPUFlag::Carry));
+
+    // LDA #$01, STA $10, LSR $10, ROR $10
+    let program = vec![0xa9, 0x01, 0x85, 0x10, 0x46, 0x10, 0x66, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.memory.read_byte(0x10), 0x80);
+    assert!(!cpu.get_cpu_flag(CPUFlag::Carry));
+    assert!(cpu.get_cpu_flag(CPUFlag::Negative));
+}
+
+#[test]
+fn test_inc_dec() {
+    // LDX #$ff, INX, LDY #$00, DEY, INC $20, INC $20, DEC $20
+    let program = vec![0xa2, 0xff, 0xe8, 0xa0, 0x00, 0x88, 0xe6, 0x20, 0xe6, 0x20, 0xc6, 0x20];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_x, 0x00);
+    assert_eq!(cpu.register_y, 0xff);
+    assert_eq!(cpu.memory.read_byte(0x20), 0x01);
+}
+
+#[test]
+fn test_compare() {
+    // LDA #$40, CMP #$40
+    let program = vec![0xa9, 0x40, 0xc9, 0x40];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert!(cpu.get_cpu_flag(CPUFlag::Zero));
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+
+    // LDX #$10, CPX #$20
+    let program = vec![0xa2, 0x10, 0xe0, 0x20];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert!(!cpu.get_cpu_flag(CPUFlag::Zero));
+    assert!(!cpu.get_cpu_flag(CPUFlag::Carry));
+    assert!(cpu.get_cpu_flag(CPUFlag::Negative));
+}
+
+#[test]
+fn test_op_bit() {
+    // LDA #$c0, STA $30, LDA #$01, BIT $30
+    let program = vec![0xa9, 0xc0, 0x85, 0x30, 0xa9, 0x01, 0x24, 0x30];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert!(cpu.get_cpu_flag(CPUFlag::Zero));
+    assert!(cpu.get_cpu_flag(CPUFlag::Overflow));
+    assert!(cpu.get_cpu_flag(CPUFlag::Negative));
+}
+
+#[test]
+fn test_branches() {
+    // LDX #$05, loop: DEX, BNE loop, INY
+    let program = vec![0xa2, 0x05, 0xca, 0xd0, 0xfd, 0xc8];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_x, 0x00);
+    assert_eq!(cpu.register_y, 0x01);
+}
+
+#[test]
+fn test_jsr_rts() {
+    // JSR $8006, LDY #$02, BRK, sub: LDX #$01, RTS
+    let program = vec![0x20, 0x06, 0x80, 0xa0, 0x02, 0x00, 0xa2, 0x01, 0x60];
+    let mut cpu = load_test_program_to_cpu(program);
+    let stack_pointer = cpu.stack_pointer;
+    cpu.run_until(StopCondition::Opcode(0x00));
+    assert_eq!(cpu.register_x, 0x01);
+    assert_eq!(cpu.register_y, 0x02);
+    assert_eq!(cpu.stack_pointer, stack_pointer);
+    assert_eq!(cpu.program_counter, 0x8005);
+}
+
+#[test]
+fn test_op_jmp_indirect_page_bug() {
+    // JMP ($10ff) reads the high byte from $1000, not $1100
+    let program = vec![0x6c, 0xff, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.memory.write_byte(0x10ff, 0x34);
+    cpu.memory.write_byte(0x1000, 0x12);
+    cpu.memory.write_byte(0x1100, 0x56);
+    cpu.run_until(StopCondition::Opcode(0x00));
+    assert_eq!(cpu.program_counter, 0x1234);
+}
+
+#[test]
+fn test_flag_instructions() {
+    // SEC, SED, SEI, CLD
+    let program = vec![0x38, 0xf8, 0x78, 0xd8];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+    assert!(!cpu.get_cpu_flag(CPUFlag::Decimal));
+    assert!(cpu.get_cpu_flag(CPUFlag::InterruptDisable));
+}
+
+#[test]
+fn test_cycles() {
+    // LDX #$01, LDA $80ff,X (page crossed), LDA $8000,X, STA $80ff,X
+    let program = vec![0xa2, 0x01, 0xbd, 0xff, 0x80, 0xbd, 0x00, 0x80, 0x9d, 0xff, 0x80];
+    let mut cpu = load_test_program_to_cpu(program);
+    let start_cycles = cpu.cycles;
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 5 });
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 4 });
+    // stores always take the fixed cycle count
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 5 });
+    assert_eq!(cpu.cycles - start_cycles, 16);
+}
+
+#[test]
+fn test_cycles_indirect_y() {
+    // LDY #$10, LDA ($20),Y with the pointer at $20 set to $80f8
+    let program = vec![0xa0, 0x10, 0xb1, 0x20];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.memory.write_word(0x20, 0x80f8);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 6 });
+}
+
+#[test]
+fn test_cycles_branch() {
+    // BNE not taken
+    let program = vec![0xa9, 0x00, 0xd0, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+
+    // BEQ taken on the same page
+    let program = vec![0xa9, 0x00, 0xf0, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 3 });
+
+    // BEQ taken backwards onto the previous page
+    let program = vec![0xa9, 0x00, 0xf0, 0x80];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 4 });
+    assert_eq!(cpu.program_counter, 0x7f84);
+}
+
+#[test]
+fn test_step_result() {
+    // LDA #$01, BRK
+    let program = vec![0xa9, 0x01, 0x00];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.memory.write_word(0xFFFE, 0x9000);
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+    assert_eq!(cpu.step(), StepResult::Break { cycles: 7 });
+    assert_eq!(cpu.program_counter, 0x9000);
+
+    // unofficial opcode is reported and not executed when they are disabled
+    let program = vec![0x07, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.unofficial_opcodes = false;
+    assert_eq!(cpu.step(), StepResult::IllegalOpcode(0x07));
+    assert_eq!(cpu.program_counter, 0x8000);
+}
+
+#[test]
+fn test_load_long_program() {
+    // 32 KiB of NOPs and one more byte
+    let mut program = vec![0xea; 0x8000];
+    program.push(0x42);
+    let mut cpu = load_test_program_to_cpu(program);
+    assert_eq!(cpu.memory.read_byte(0x0000), 0x42);
+    assert_eq!(cpu.program_counter, 0x8000);
+}
+
+#[test]
+fn test_run_until() {
+    // LDX #$00, loop: INX, JMP loop
+    let program = vec![0xa2, 0x00, 0xe8, 0x4c, 0x02, 0x80];
+
+    let mut cpu = load_test_program_to_cpu(program.clone());
+    assert_eq!(cpu.run_until(StopCondition::Instructions(5)), StopReason::ConditionMet);
+    assert_eq!(cpu.register_x, 2);
+
+    let mut cpu = load_test_program_to_cpu(program.clone());
+    let start_cycles = cpu.cycles;
+    assert_eq!(cpu.run_until(StopCondition::Cycles(12)), StopReason::ConditionMet);
+    assert_eq!(cpu.cycles - start_cycles, 12);
+    assert_eq!(cpu.register_x, 2);
+
+    let mut cpu = load_test_program_to_cpu(program);
+    assert_eq!(cpu.run_until(StopCondition::ProgramCounter(0x8003)), StopReason::ConditionMet);
+    assert_eq!(cpu.register_x, 1);
+
+    // LDA #$05, BRK
+    let program = vec![0xa9, 0x05, 0x00];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.memory.write_word(0xFFFE, 0x9000);
+    assert_eq!(cpu.run_until(StopCondition::Break), StopReason::ConditionMet);
+    assert_eq!(cpu.program_counter, 0x9000);
+
+    // LDA #$05, *SLO $10
+    let program = vec![0xa9, 0x05, 0x07, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.unofficial_opcodes = false;
+    assert_eq!(cpu.run_until(StopCondition::Break), StopReason::IllegalOpcode(0x07));
+    assert_eq!(cpu.program_counter, 0x8002);
+}
+
+#[test]
+fn test_run_until_skips_interrupts() {
+    // NOP, NOP, NOP, handler: INX, INX
+    let mut cpu = load_test_program_to_cpu(vec![0xea, 0xea, 0xea]);
+    cpu.set_nmi_vector(0x9000);
+    cpu.memory.write_byte(0x9000, 0xe8);
+    cpu.memory.write_byte(0x9001, 0xe8);
+    cpu.set_nmi_line(true);
+    // the NMI is taken after the first NOP and does not count as an instruction
+    assert_eq!(cpu.run_until(StopCondition::Instructions(3)), StopReason::ConditionMet);
+    assert_eq!(cpu.program_counter, 0x9002);
+    assert_eq!(cpu.register_x, 2);
+}
+
+#[test]
+fn test_run_executes_brk() {
+    // LDA #$05, BRK, padding, handler: INX
+    let mut cpu = load_test_program_to_cpu(vec![0xa9, 0x05, 0x00, 0xff]);
+    cpu.memory.write_word(0xFFFE, 0x9000);
+    cpu.memory.write_byte(0x9000, 0xe8);
+    let stack_pointer = cpu.stack_pointer;
+    cpu.run();
+    // run returns once BRK has entered the handler
+    assert_eq!(cpu.program_counter, 0x9000);
+    assert_eq!(cpu.register_x, 0);
+    // return address and status with B set were pushed
+    assert_eq!(cpu.stack_pointer, stack_pointer.wrapping_sub(3));
+    let stack = 0x0100 + cpu.stack_pointer as u16;
+    assert_eq!(cpu.memory.read_byte(stack + 1) & 0b0001_0000, 0b0001_0000);
+    assert_eq!(cpu.memory.read_word(stack + 2), 0x8004);
+
+    // stopping on the opcode leaves the BRK unexecuted
+    let mut cpu = load_test_program_to_cpu(vec![0xa9, 0x05, 0x00]);
+    assert_eq!(cpu.run_until(StopCondition::Opcode(0x00)), StopReason::ConditionMet);
+    assert_eq!(cpu.program_counter, 0x8002);
+}
+
+#[test]
+fn test_decimal_mode() {
+    // SED, CLC, LDA #$58, ADC #$46
+    let program = vec![0xf8, 0x18, 0xa9, 0x58, 0x69, 0x46];
+    let mut cpu = load_test_program_to_cpu(program.clone());
+    cpu.variant = CPUVariant::NMOS6502;
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x04);
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+
+    // the 2A03 has no decimal mode
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x9e);
+    assert!(!cpu.get_cpu_flag(CPUFlag::Carry));
+
+    // SED, SEC, LDA #$12, SBC #$21
+    let program = vec![0xf8, 0x38, 0xa9, 0x12, 0xe9, 0x21];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.variant = CPUVariant::NMOS6502;
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x91);
+    assert!(!cpu.get_cpu_flag(CPUFlag::Carry));
+}
+
+#[test]
+fn test_load_image_and_trap() {
+    let mut cpu = CPU::new();
+    // INX, INX, BNE * at $0400
+    cpu.load_image(0x0400, &[0xe8, 0xe8, 0xd0, 0xfe]);
+    cpu.program_counter = 0x0400;
+    assert_eq!(cpu.run_until(StopCondition::Trap), StopReason::ConditionMet);
+    assert_eq!(cpu.program_counter, 0x0402);
+    assert_eq!(cpu.register_x, 2);
+    // the image wraps around the end of the address space, vectors are untouched
+    cpu.load_image(0xFFFF, &[0x11, 0x22]);
+    assert_eq!(cpu.memory.read_byte(0xFFFF), 0x11);
+    assert_eq!(cpu.memory.read_byte(0x0000), 0x22);
+    assert_eq!(cpu.memory.read_word(0xFFFC), 0x0000);
+}
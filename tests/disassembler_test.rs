@@ -0,0 +1,62 @@
+use nesguin::emu6502::cpu::CPU;
+use nesguin::emu6502::disassembler::{disassemble, disassemble_bytes, disassemble_one};
+
+#[test]
+fn test_addressing_mode_syntax() {
+    let cases:[(&[u8], &str); 13] = [
+        (&[0xea], "NOP"),
+        (&[0x0a], "ASL A"),
+        (&[0xa9, 0x10], "LDA #$10"),
+        (&[0xa5, 0x40], "LDA $40"),
+        (&[0xb5, 0x40], "LDA $40,X"),
+        (&[0xb6, 0x40], "LDX $40,Y"),
+        (&[0x8d, 0x00, 0x02], "STA $0200"),
+        (&[0x9d, 0x00, 0x02], "STA $0200,X"),
+        (&[0xb9, 0x34, 0x12], "LDA $1234,Y"),
+        (&[0x6c, 0xfc, 0xff], "JMP ($FFFC)"),
+        (&[0xa1, 0x40], "LDA ($40,X)"),
+        (&[0xb1, 0x40], "LDA ($40),Y"),
+        (&[0xa7, 0x40], "*LAX $40")
+    ];
+    for (bytes, expected) in cases {
+        let line = &disassemble_bytes(bytes, 0x8000)[0];
+        assert_eq!(line.to_string(), expected);
+        assert_eq!(line.bytes(), bytes);
+    }
+}
+
+#[test]
+fn test_targets() {
+    // BNE back to $8000, BPL forward, JSR $C123
+    let lines = disassemble_bytes(&[0xd0, 0xfe, 0x10, 0x7f, 0x20, 0x23, 0xc1], 0x8000);
+    assert_eq!(lines[0].to_string(), "BNE $8000");
+    assert_eq!(lines[0].target, Some(0x8000));
+    assert_eq!(lines[1].to_string(), "BPL $8083");
+    assert_eq!(lines[2].target, Some(0xC123));
+    assert!(!lines[0].unofficial);
+}
+
+#[test]
+fn test_truncated_instruction_is_data() {
+    let lines = disassemble_bytes(&[0xe8, 0xad, 0x00], 0xFFFD);
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[1].listing(), "FFFE  AD        .byte $AD");
+    assert_eq!(lines[2].address, 0xFFFF);
+}
+
+#[test]
+fn test_disassemble_bus() {
+    let mut cpu = CPU::new();
+    // LDX #$00, INX, BNE $8002, JAM
+    cpu.load_program(vec![0xa2, 0x00, 0xe8, 0xd0, 0xfd, 0x02]);
+    let line = disassemble_one(&cpu.memory, 0x8003);
+    assert_eq!(line.listing(), "8003  D0 FD     BNE $8002");
+    assert_eq!(line.next_address(), 0x8005);
+
+    let lines = disassemble(&cpu.memory, 0x8000, 0x8005);
+    let text:Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    assert_eq!(text, vec!["LDX #$00", "INX", "BNE $8002", "*JAM"]);
+    // the range stops at the end of the address space
+    let lines = disassemble(&cpu.memory, 0xFFFE, 0xFFFF);
+    assert_eq!(lines.iter().map(|line| line.address).collect::<Vec<u16>>(), vec![0xFFFE, 0xFFFF]);
+}
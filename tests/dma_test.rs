@@ -0,0 +1,208 @@
+use nesguin::bus::NesBus;
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::{CPU, StepResult};
+use nesguin::emu6502::ram::RAM;
+
+/***
+ * Flat RAM that halts the CPU once on a read of the given address, records
+ * every read including the halted one.
+ */
+struct HaltTestBus {
+    ram:RAM,
+    halt_on:Option<u16>,
+    reads:Vec<u16>
+}
+
+impl Bus for HaltTestBus {
+    fn read(&mut self, addr:u16) -> u8 {
+        self.reads.push(addr);
+        self.ram.read(addr)
+    }
+
+    fn write(&mut self, addr:u16, data:u8) {
+        self.ram.write(addr, data);
+    }
+
+    fn peek(&self, addr:u16) -> u8 {
+        self.ram.peek(addr)
+    }
+
+    fn halt(&mut self, addr:u16) -> u16 {
+        if self.halt_on != Some(addr) {
+            return 0;
+        }
+        self.halt_on = None;
+        self.read(addr);
+        4
+    }
+}
+
+fn halt_test_cpu(program: Vec<u8>) -> CPU<HaltTestBus> {
+    let bus = HaltTestBus { ram: RAM::new(), halt_on: None, reads: Vec::new() };
+    let mut cpu = CPU::with_bus(bus);
+    cpu.load_program(program);
+    cpu.reset();
+    cpu.memory.reads.clear();
+    cpu
+}
+
+/***
+ * Helper method for a CPU on the NES bus with page $02 filled with 0-255.
+ */
+fn load_dma_program(program: Vec<u8>) -> CPU<NesBus> {
+    let mut cpu = CPU::with_bus(NesBus::new());
+    cpu.load_program(program);
+    cpu.reset();
+    for offset in 0..0x100 {
+        cpu.memory.ram[0x200 + offset] = offset as u8;
+    }
+    cpu
+}
+
+#[test]
+fn test_oam_dma_copies_page() {
+    // LDA #$02, STA $4014, NOP
+    let mut cpu = load_dma_program(vec![0xa9, 0x02, 0x8d, 0x14, 0x40, 0xea]);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 4 });
+    // the copy has not happened when the write instruction completes
+    assert_eq!(cpu.memory.ppu.oam[0xFF], 0);
+    let expected = if cpu.is_odd_cycle() { 514 } else { 513 };
+    let start_cycles = cpu.cycles;
+    assert_eq!(cpu.step(), StepResult::Stalled { cycles: expected });
+    assert_eq!(cpu.cycles - start_cycles, expected as u64);
+    assert_eq!(cpu.memory.ppu.oam.to_vec(), (0..=255).collect::<Vec<u8>>());
+    assert_eq!(cpu.program_counter, 0x8005);
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+}
+
+#[test]
+fn test_oam_dma_cycle_parity() {
+    let mut stalls = Vec::new();
+    // LDA #$02, STA $4014 with and without a 3 cycle LDA $00 in front
+    for program in [vec![0xa9, 0x02, 0x8d, 0x14, 0x40], vec![0xa5, 0x00, 0xa9, 0x02, 0x8d, 0x14, 0x40]] {
+        let steps = program.len() / 2;
+        let mut cpu = load_dma_program(program);
+        for _ in 0..steps {
+            cpu.step();
+        }
+        let odd = cpu.is_odd_cycle();
+        let result = cpu.step();
+        assert_eq!(result, StepResult::Stalled { cycles: if odd { 514 } else { 513 } });
+        stalls.push(result.cycles());
+    }
+    stalls.sort();
+    assert_eq!(stalls, vec![513, 514]);
+}
+
+#[test]
+fn test_oam_dma_starts_at_oam_addr() {
+    // LDA #$10, STA $2003, LDA #$02, STA $4014
+    let mut cpu = load_dma_program(vec![0xa9, 0x10, 0x8d, 0x03, 0x20, 0xa9, 0x02, 0x8d, 0x14, 0x40]);
+    for _ in 0..5 {
+        cpu.step();
+    }
+    assert_eq!(cpu.memory.ppu.oam[0x10], 0x00);
+    assert_eq!(cpu.memory.ppu.oam[0x0F], 0xFF);
+    assert_eq!(cpu.memory.ppu.oam_addr, 0x10);
+}
+
+#[test]
+fn test_stall() {
+    let mut cpu = CPU::new();
+    cpu.load_program(vec![0xea]);
+    cpu.reset();
+    cpu.stall(10);
+    let start_cycles = cpu.cycles;
+    assert_eq!(cpu.step(), StepResult::Stalled { cycles: 10 });
+    assert_eq!(cpu.cycles - start_cycles, 10);
+    assert_eq!(cpu.program_counter, 0x8000);
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+
+    // a flat RAM has no DMA unit
+    let mut cpu = CPU::new();
+    cpu.load_program(vec![0x8d, 0x14, 0x40, 0xea]);
+    cpu.reset();
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+
+    // stalls piling up saturate instead of overflowing
+    let mut cpu = CPU::new();
+    cpu.stall(u16::MAX);
+    cpu.stall(10);
+    assert_eq!(cpu.step(), StepResult::Stalled { cycles: u16::MAX });
+}
+
+#[test]
+fn test_halt_mid_instruction() {
+    // LDA $4016, NOP
+    let mut cpu = halt_test_cpu(vec![0xad, 0x16, 0x40, 0xea]);
+    cpu.memory.halt_on = Some(0x4016);
+    let start_cycles = cpu.cycles;
+    // the halt lands on the operand read and is reported by the next step
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 4 });
+    assert_eq!(cpu.cycles - start_cycles, 8);
+    assert_eq!(cpu.memory.reads, vec![0x8000, 0x8001, 0x8002, 0x4016, 0x4016]);
+    assert_eq!(cpu.step(), StepResult::Stalled { cycles: 4 });
+    assert_eq!(cpu.cycles - start_cycles, 8);
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+}
+
+#[test]
+fn test_halt_on_opcode_fetch() {
+    // NOP, NOP
+    let mut cpu = halt_test_cpu(vec![0xea, 0xea]);
+    cpu.step();
+    cpu.memory.halt_on = Some(0x8001);
+    assert_eq!(cpu.step(), StepResult::Stalled { cycles: 4 });
+    assert_eq!(cpu.program_counter, 0x8001);
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+}
+
+#[test]
+fn test_dmc_dma_on_nes_bus() {
+    // LDA #$10, STA $4015, NOP
+    let mut cpu = load_dma_program(vec![0xa9, 0x10, 0x8d, 0x15, 0x40, 0xea]);
+    cpu.memory.write(0xC000, 0xFF);
+    cpu.memory.write(0x4012, 0x00);
+    cpu.memory.write(0x4013, 0x00);
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.memory.peek(0x4015) & 0x10, 0x10);
+    // the request made by the write halts the next read, the fetch is aligned to an odd cycle
+    let expected = if cpu.is_odd_cycle() { 3 } else { 4 };
+    let start_cycles = cpu.cycles;
+    assert_eq!(cpu.step(), StepResult::Stalled { cycles: expected });
+    assert_eq!(cpu.cycles - start_cycles, expected as u64);
+    // the single byte sample has been fetched
+    assert_eq!(cpu.memory.peek(0x4015) & 0x10, 0x00);
+    assert_eq!(cpu.memory.apu.dmc.bytes_remaining, 0);
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+}
+
+#[test]
+fn test_dmc_dma_during_oam_dma() {
+    // LDA #$10, STA $4015, LDA #$02, STA $4014, NOP
+    let mut cpu = CPU::with_bus(NesBus::new());
+    // fastest rate from power on and a 17 byte sample
+    cpu.memory.write(0x4010, 0x0f);
+    cpu.memory.write(0x4013, 0x01);
+    cpu.load_program(vec![0xa9, 0x10, 0x8d, 0x15, 0x40, 0xa9, 0x02, 0x8d, 0x14, 0x40, 0xea]);
+    cpu.reset();
+    for offset in 0..0x100 {
+        cpu.memory.ram[0x200 + offset] = offset as u8;
+    }
+    cpu.step();
+    cpu.step();
+    // the first sample byte is fetched right away
+    assert!(matches!(cpu.step(), StepResult::Stalled { cycles: 3 | 4 }));
+    assert_eq!(cpu.memory.apu.dmc.bytes_remaining, 16);
+    cpu.step();
+    cpu.step();
+    // the second comes due while the page is copied and takes one of its reads
+    let expected = if cpu.is_odd_cycle() { 516 } else { 515 };
+    assert_eq!(cpu.step(), StepResult::Stalled { cycles: expected });
+    assert_eq!(cpu.memory.apu.dmc.bytes_remaining, 15);
+    assert_eq!(cpu.memory.ppu.oam.to_vec(), (0..=255).collect::<Vec<u8>>());
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+}
@@ -0,0 +1,178 @@
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::{CPU, CPUFlag, Interrupt, StepResult, IRQ_VECTOR, NMI_VECTOR};
+use nesguin::emu6502::ram::RAM;
+
+const NMI_HANDLER:u16 = 0x9000;
+const IRQ_HANDLER:u16 = 0xA000;
+
+/***
+ * Helper method for creating a CPU with a program and both interrupt vectors set.
+ */
+fn load_test_program_to_cpu(program: Vec<u8>) -> CPU {
+    let mut cpu = CPU::new();
+    cpu.reset();
+    cpu.load_program(program);
+    cpu.memory.write_word(NMI_VECTOR, NMI_HANDLER);
+    cpu.memory.write_word(IRQ_VECTOR, IRQ_HANDLER);
+    // handlers start with NOPs
+    for offset in 0..4 {
+        cpu.memory.write_byte(NMI_HANDLER + offset, 0xea);
+        cpu.memory.write_byte(IRQ_HANDLER + offset, 0xea);
+    }
+    cpu
+}
+
+/***
+ * Flat RAM that asserts NMI on the first stack write once armed.
+ */
+struct NmiOnPushBus {
+    ram:RAM,
+    armed:bool,
+    nmi:bool
+}
+
+impl Bus for NmiOnPushBus {
+    fn read(&mut self, addr:u16) -> u8 {
+        self.ram.read(addr)
+    }
+
+    fn write(&mut self, addr:u16, data:u8) {
+        if self.armed && (0x0100..=0x01FF).contains(&addr) {
+            self.nmi = true;
+        }
+        self.ram.write(addr, data);
+    }
+
+    fn peek(&self, addr:u16) -> u8 {
+        self.ram.peek(addr)
+    }
+
+    fn nmi(&self) -> bool {
+        self.nmi
+    }
+}
+
+#[test]
+fn test_reset() {
+    let mut cpu = load_test_program_to_cpu(vec![0xea]);
+    cpu.reset();
+    assert_eq!(cpu.program_counter, 0x8000);
+    assert_eq!(cpu.stack_pointer, 0xfd);
+    assert!(cpu.get_cpu_flag(CPUFlag::InterruptDisable));
+}
+
+#[test]
+fn test_brk_and_rti() {
+    // CLC, BRK, padding, LDX #$01
+    let mut cpu = load_test_program_to_cpu(vec![0x18, 0x00, 0xff, 0xa2, 0x01]);
+    // handler: SEC, RTI
+    cpu.memory.write_byte(IRQ_HANDLER, 0x38);
+    cpu.memory.write_byte(IRQ_HANDLER + 1, 0x40);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Break { cycles: 7 });
+    assert_eq!(cpu.program_counter, IRQ_HANDLER);
+    assert!(cpu.get_cpu_flag(CPUFlag::InterruptDisable));
+    // return address skips the padding byte, pushed status has B set
+    let stack = cpu.stack_pointer as u16 + 0x0100;
+    assert_eq!(cpu.memory.read_byte(stack + 1) & 0b0011_0000, 0b0011_0000);
+    assert_eq!(cpu.memory.read_word(stack + 2), 0x8003);
+
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 6 });
+    assert_eq!(cpu.program_counter, 0x8003);
+    // RTI restores the flags pushed by BRK
+    assert!(!cpu.get_cpu_flag(CPUFlag::Carry));
+    assert!(cpu.get_cpu_flag(CPUFlag::InterruptDisable));
+    cpu.step();
+    assert_eq!(cpu.register_x, 0x01);
+}
+
+#[test]
+fn test_nmi() {
+    // NOP, NOP
+    let mut cpu = load_test_program_to_cpu(vec![0xea, 0xea]);
+    cpu.set_nmi_line(true);
+    // the line is polled at the end of the instruction
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+    assert_eq!(cpu.step(), StepResult::Interrupt { kind: Interrupt::NMI, cycles: 7 });
+    assert_eq!(cpu.program_counter, NMI_HANDLER);
+    // pushed status has B clear
+    let stack = cpu.stack_pointer as u16 + 0x0100;
+    assert_eq!(cpu.memory.read_byte(stack + 1) & 0b0011_0000, 0b0010_0000);
+    assert_eq!(cpu.memory.read_word(stack + 2), 0x8001);
+
+    // holding the line does not trigger another NMI
+    cpu.step();
+    cpu.step();
+    assert_eq!(cpu.program_counter, NMI_HANDLER + 2);
+}
+
+#[test]
+fn test_nmi_ignores_interrupt_disable() {
+    // SEI, NOP
+    let mut cpu = load_test_program_to_cpu(vec![0x78, 0xea]);
+    cpu.set_nmi_line(true);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Interrupt { kind: Interrupt::NMI, cycles: 7 });
+}
+
+#[test]
+fn test_irq() {
+    // NOP, NOP
+    let mut cpu = load_test_program_to_cpu(vec![0xea, 0xea]);
+    // masked after reset
+    cpu.set_irq_line(true);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+
+    // CLI, NOP, NOP: the IRQ is taken after the instruction following CLI
+    let mut cpu = load_test_program_to_cpu(vec![0x58, 0xea, 0xea]);
+    cpu.set_irq_line(true);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+    assert_eq!(cpu.step(), StepResult::Interrupt { kind: Interrupt::IRQ, cycles: 7 });
+    assert_eq!(cpu.program_counter, IRQ_HANDLER);
+    assert!(cpu.get_cpu_flag(CPUFlag::InterruptDisable));
+
+    // releasing the line before the poll drops the request
+    let mut cpu = load_test_program_to_cpu(vec![0x58, 0xea, 0xea]);
+    cpu.set_irq_line(true);
+    cpu.step();
+    cpu.set_irq_line(false);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+}
+
+#[test]
+fn test_nmi_before_brk() {
+    // NOP, BRK
+    let mut cpu = load_test_program_to_cpu(vec![0xea, 0x00]);
+    cpu.step();
+    // NMI edge arrives after the poll, before BRK starts
+    cpu.set_nmi_line(true);
+    assert_eq!(cpu.step(), StepResult::Break { cycles: 7 });
+    assert_eq!(cpu.program_counter, IRQ_HANDLER);
+    // the NMI is taken once BRK completes
+    assert_eq!(cpu.step(), StepResult::Interrupt { kind: Interrupt::NMI, cycles: 7 });
+    assert_eq!(cpu.program_counter, NMI_HANDLER);
+}
+
+#[test]
+fn test_nmi_hijacks_brk() {
+    // NOP, BRK
+    let mut cpu = CPU::with_bus(NmiOnPushBus { ram: RAM::new(), armed: false, nmi: false });
+    cpu.load_program(vec![0xea, 0x00]);
+    cpu.reset();
+    cpu.memory.write_word(NMI_VECTOR, NMI_HANDLER);
+    cpu.memory.write_word(IRQ_VECTOR, IRQ_HANDLER);
+    cpu.memory.write(NMI_HANDLER, 0xea);
+    cpu.step();
+    // NMI edge arrives while BRK pushes to the stack
+    cpu.memory.armed = true;
+    assert_eq!(cpu.step(), StepResult::Break { cycles: 7 });
+    assert_eq!(cpu.program_counter, NMI_HANDLER);
+    // the pushed status still has B set, and the NMI is not taken again
+    let stack = cpu.stack_pointer as u16 + 0x0100;
+    assert_eq!(cpu.memory.read(stack + 1) & 0b0001_0000, 0b0001_0000);
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+}
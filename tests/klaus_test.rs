@@ -0,0 +1,77 @@
+use std::fs;
+use std::path::PathBuf;
+
+use nesguin::emu6502::cpu::{CPUVariant, StopCondition, StopReason, CPU};
+
+/***
+ * Klaus Dormann's 6502 test suites assembled with their default options.
+ * The binaries are not redistributed with the sources, put them in
+ * tests/roms or point KLAUS_DIR at them and run the ignored tests.
+ */
+fn klaus_dir() -> PathBuf {
+    std::env::var("KLAUS_DIR").map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms"))
+}
+
+/***
+ * Where a suite is loaded and started and how it reports its result.
+ */
+struct Suite {
+    file:&'static str,
+    load_address:u16,
+    entry_point:u16,
+    /* Trap reached when every test passed, any other trap is a failure */
+    success_trap:Option<u16>,
+    /* Zero page byte holding the test number, or the error flag for the decimal test */
+    result_address:u16
+}
+
+/***
+ * Runs the suite to its first trap.
+ */
+fn run_suite(suite: &Suite) -> CPU {
+    let image = fs::read(klaus_dir().join(suite.file))
+        .unwrap_or_else(|error| panic!("cannot read {} in {}: {}", suite.file, klaus_dir().display(), error));
+    let mut cpu = CPU::new();
+    // the suites test decimal mode, which the 2A03 lacks
+    cpu.variant = CPUVariant::NMOS6502;
+    cpu.load_image(suite.load_address, &image);
+    cpu.reset();
+    cpu.program_counter = suite.entry_point;
+    let reason = cpu.run_until(StopCondition::Trap);
+    assert_eq!(reason, StopReason::ConditionMet, "{} stopped at ${:04X}", suite.file, cpu.program_counter);
+    cpu
+}
+
+#[test]
+#[ignore = "needs 6502_functional_test.bin, see klaus_dir"]
+fn test_functional_suite() {
+    let suite = Suite {
+        file: "6502_functional_test.bin",
+        load_address: 0x0000,
+        entry_point: 0x0400,
+        success_trap: Some(0x3469),
+        result_address: 0x0200
+    };
+    let mut cpu = run_suite(&suite);
+    let test_case = cpu.memory.read_byte(suite.result_address);
+    assert_eq!(Some(cpu.program_counter), suite.success_trap,
+        "trapped at ${:04X} in test case ${:02X}", cpu.program_counter, test_case);
+}
+
+#[test]
+#[ignore = "needs 6502_decimal_test.bin, see klaus_dir"]
+fn test_decimal_suite() {
+    let suite = Suite {
+        file: "6502_decimal_test.bin",
+        load_address: 0x0000,
+        entry_point: 0x0200,
+        success_trap: None,
+        result_address: 0x000B
+    };
+    let mut cpu = run_suite(&suite);
+    let error = cpu.memory.read_byte(suite.result_address);
+    // operands of the failing addition are left in N1, N2 and the carry in Y
+    assert_eq!(error, 0, "trapped at ${:04X}, failed on {:02X} + {:02X} carry {}",
+        cpu.program_counter, cpu.memory.read_byte(0x0000), cpu.memory.read_byte(0x0001), cpu.register_y);
+}
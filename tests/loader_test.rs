@@ -0,0 +1,93 @@
+use nesguin::emu6502::cpu::{CPU, StopCondition, StopReason};
+use nesguin::emu6502::loader::{Image, ImageFormat, LoaderError, Segment};
+
+#[test]
+fn test_intel_hex() {
+    let text = ":03040000A901E867\n:02040300D0FE29\n:01200000409F\n:0400000500000400F3\n:00000001FF\n";
+    let image = Image::from_intel_hex(text).unwrap();
+    // adjacent records are merged
+    assert_eq!(image.segments, vec![
+        Segment { address: 0x0400, data: vec![0xa9, 0x01, 0xe8, 0xd0, 0xfe] },
+        Segment { address: 0x2000, data: vec![0x40] }
+    ]);
+    assert_eq!(image.entry_point, Some(0x0400));
+}
+
+#[test]
+fn test_intel_hex_errors() {
+    assert!(matches!(Image::from_intel_hex(":03040000A901E868"), Err(LoaderError::ChecksumMismatch { line: 1 })));
+    assert!(matches!(Image::from_intel_hex("\n03040000A901E867"), Err(LoaderError::InvalidRecord { line: 2 })));
+    assert!(matches!(Image::from_intel_hex(":0304000A901E867"), Err(LoaderError::InvalidRecord { line: 1 })));
+    // data above 64 KiB after an extended linear address record
+    assert!(matches!(Image::from_intel_hex(":020000040001F9\n:0100000001FE"),
+        Err(LoaderError::AddressOutOfRange { line: 2, address: 0x10000 })));
+    // the end of the data does not wrap around past 4 GiB
+    assert!(matches!(Image::from_intel_hex(":02000004FFFFFC\n:02FFFF00AABB9B"),
+        Err(LoaderError::AddressOutOfRange { line: 2, address: 0xFFFFFFFF })));
+    // start addresses are not truncated to 16 bits
+    assert!(matches!(Image::from_intel_hex(":0400000500010000F6"),
+        Err(LoaderError::AddressOutOfRange { line: 1, address: 0x10000 })));
+    assert!(matches!(Image::from_intel_hex(":04000003F0001000F9"),
+        Err(LoaderError::AddressOutOfRange { line: 1, address: 0xF1000 })));
+    // only hex digits, "+1" would parse as a number
+    assert!(matches!(Image::from_intel_hex(":01000000+1FE"), Err(LoaderError::InvalidRecord { line: 1 })));
+}
+
+#[test]
+fn test_srecord() {
+    let text = "S00600004844521B\nS1060400A901E863\nS206000403D0FE24\nS5030002FA\nS9030400F8\n";
+    let image = Image::from_srecord(text).unwrap();
+    assert_eq!(image.segments, vec![Segment { address: 0x0400, data: vec![0xa9, 0x01, 0xe8, 0xd0, 0xfe] }]);
+    assert_eq!(image.entry_point, Some(0x0400));
+
+    assert!(matches!(Image::from_srecord("S1060400A901E864"), Err(LoaderError::ChecksumMismatch { line: 1 })));
+    assert!(matches!(Image::from_srecord("S4030002FA"), Err(LoaderError::UnsupportedRecord { line: 1, kind: 4 })));
+    assert!(matches!(Image::from_srecord("X1060400A901E863"), Err(LoaderError::InvalidRecord { line: 1 })));
+    assert!(matches!(Image::from_srecord("S70500010000F9"), Err(LoaderError::AddressOutOfRange { line: 1, address: 0x10000 })));
+}
+
+#[test]
+fn test_prg_and_raw() {
+    let image = Image::from_prg(&[0x01, 0x08, 0xea, 0x60]).unwrap();
+    assert_eq!(image.segments, vec![Segment { address: 0x0801, data: vec![0xea, 0x60] }]);
+    assert_eq!(image.entry_point, None);
+    assert!(matches!(Image::from_prg(&[0x01]), Err(LoaderError::MissingLoadAddress)));
+
+    assert_eq!(Image::raw(0xF000, &[1, 2]).segments, vec![Segment { address: 0xF000, data: vec![1, 2] }]);
+    assert_eq!(ImageFormat::from_extension("firmware.HEX"), Some(ImageFormat::IntelHex));
+    assert_eq!(ImageFormat::from_extension("test.s19"), Some(ImageFormat::SRecord));
+    assert_eq!(ImageFormat::from_extension("game.prg"), Some(ImageFormat::Prg));
+    assert_eq!(ImageFormat::from_extension("rom.bin"), None);
+}
+
+#[test]
+fn test_load_image_file() {
+    let path = std::env::temp_dir().join(format!("nesguin_loader_{}.prg", std::process::id()));
+    std::fs::write(&path, [0x00, 0x03, 0xe8]).unwrap();
+    let image = Image::load(&path, ImageFormat::Prg).unwrap();
+    let raw = Image::load(&path, ImageFormat::Raw(0x1000)).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(image.segments, vec![Segment { address: 0x0300, data: vec![0xe8] }]);
+    assert_eq!(raw.segments, vec![Segment { address: 0x1000, data: vec![0x00, 0x03, 0xe8] }]);
+    assert!(matches!(Image::load(&path, ImageFormat::Prg), Err(LoaderError::Io(_))));
+}
+
+#[test]
+fn test_cpu_load_and_vectors() {
+    let image = Image::from_srecord("S1060400A901E863\nS206000403D0FE24\nS9030400F8").unwrap();
+    let mut cpu = CPU::new();
+    cpu.load(&image);
+    cpu.set_reset_vector(image.entry_point.unwrap());
+    cpu.set_nmi_vector(0x1234);
+    cpu.set_irq_vector(0x5678);
+    assert_eq!(cpu.memory.read_word(0xFFFA), 0x1234);
+    assert_eq!(cpu.memory.read_word(0xFFFC), 0x0400);
+    assert_eq!(cpu.memory.read_word(0xFFFE), 0x5678);
+
+    cpu.reset();
+    assert_eq!(cpu.program_counter, 0x0400);
+    assert_eq!(cpu.run_until(StopCondition::Trap), StopReason::ConditionMet);
+    assert_eq!(cpu.program_counter, 0x0403);
+    assert_eq!(cpu.register_a, 0x01);
+    assert_eq!(cpu.register_x, 0x01);
+}
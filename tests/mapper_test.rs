@@ -0,0 +1,361 @@
+use nesguin::bus::NesBus;
+use nesguin::cartridge::{Cartridge, CartridgeError, ConsoleType, Mirroring, RomFormat, Timing};
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::{CPU, StepResult};
+use nesguin::mapper;
+
+/***
+ * Helper method for building a cartridge whose PRG and CHR banks are filled with their bank number.
+ */
+fn test_cartridge(mapper: u16, submapper: u8, prg_size: usize, prg_bank_size: usize, chr_size: usize, chr_bank_size: usize) -> Cartridge {
+    Cartridge {
+        format: RomFormat::Nes20,
+        mapper,
+        submapper,
+        mirroring: Mirroring::Vertical,
+        battery: false,
+        trainer: None,
+        prg_rom: (0..prg_size).map(|i| (i / prg_bank_size) as u8).collect(),
+        chr_rom: (0..chr_size).map(|i| (i / chr_bank_size) as u8).collect(),
+        prg_ram_size: 0,
+        prg_nvram_size: 0,
+        chr_ram_size: if chr_size == 0 { 0x2000 } else { 0 },
+        chr_nvram_size: 0,
+        timing: Timing::NTSC,
+        console_type: ConsoleType::NES
+    }
+}
+
+#[test]
+fn test_nrom() {
+    let mut cartridge = test_cartridge(0, 0, 0x4000, 0x4000, 0x2000, 0x2000);
+    cartridge.prg_rom[0x0123] = 0x42;
+    cartridge.prg_ram_size = 0x2000;
+    let mut mapper = mapper::from_cartridge(cartridge).unwrap();
+    // 16 KiB PRG is mirrored
+    assert_eq!(mapper.cpu_read(0x8123), Some(0x42));
+    assert_eq!(mapper.cpu_read(0xC123), Some(0x42));
+    // PRG ROM is not writable, PRG RAM is
+    mapper.cpu_write(0x8123, 0x00);
+    assert_eq!(mapper.cpu_read(0x8123), Some(0x42));
+    mapper.cpu_write(0x6010, 0x99);
+    assert_eq!(mapper.cpu_read(0x6010), Some(0x99));
+    assert_eq!(mapper.cpu_read(0x5000), None);
+    // CHR ROM is not writable
+    mapper.ppu_write(0x0010, 0x55);
+    assert_eq!(mapper.ppu_read(0x0010), 0x00);
+    assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+    assert!(!mapper.irq());
+
+    // a trainer gets 8 KiB of PRG RAM even when none is declared
+    let mut cartridge = test_cartridge(0, 0, 0x4000, 0x4000, 0x2000, 0x2000);
+    cartridge.trainer = Some(vec![0xab; 512]);
+    let mut mapper = mapper::from_cartridge(cartridge).unwrap();
+    assert_eq!(mapper.cpu_read(0x7000), Some(0xab));
+    assert_eq!(mapper.cpu_read(0x71FF), Some(0xab));
+    assert_eq!(mapper.cpu_read(0x7200), Some(0x00));
+    assert_eq!(mapper.cpu_read(0x6000), Some(0x00));
+}
+
+#[test]
+fn test_uxrom() {
+    let mut mapper = mapper::from_cartridge(test_cartridge(2, 1, 0x20000, 0x4000, 0, 0x2000)).unwrap();
+    assert_eq!(mapper.cpu_read(0x8000), Some(0));
+    assert_eq!(mapper.cpu_read(0xC000), Some(7));
+    mapper.cpu_write(0x8000, 5);
+    assert_eq!(mapper.cpu_read(0x8000), Some(5));
+    assert_eq!(mapper.cpu_read(0xFFFF), Some(7));
+    // CHR RAM
+    mapper.ppu_write(0x1234, 0x55);
+    assert_eq!(mapper.ppu_read(0x1234), 0x55);
+}
+
+#[test]
+fn test_uxrom_bus_conflicts() {
+    let mut cartridge = test_cartridge(2, 0, 0x20000, 0x4000, 0, 0x2000);
+    // the fixed bank holds 0x03 at $C000
+    cartridge.prg_rom[0x1C000] = 0x03;
+    let mut mapper = mapper::from_cartridge(cartridge).unwrap();
+    mapper.cpu_write(0xC000, 0x06);
+    assert_eq!(mapper.cpu_read(0x8000), Some(0x02));
+}
+
+#[test]
+fn test_cnrom() {
+    let mut mapper = mapper::from_cartridge(test_cartridge(3, 0, 0x8000, 0x8000, 0x8000, 0x2000)).unwrap();
+    assert_eq!(mapper.ppu_read(0x0000), 0);
+    // bus conflict with the 0x00 PRG byte masks the bank number
+    mapper.cpu_write(0x8000, 0x03);
+    assert_eq!(mapper.ppu_read(0x0000), 0);
+
+    let mut cartridge = test_cartridge(3, 0, 0x8000, 0x8000, 0x8000, 0x2000);
+    cartridge.prg_rom[0x7FFF] = 0xFF;
+    let mut mapper = mapper::from_cartridge(cartridge).unwrap();
+    mapper.cpu_write(0xFFFF, 0x03);
+    assert_eq!(mapper.ppu_read(0x1FFF), 3);
+}
+
+#[test]
+fn test_axrom() {
+    let mut mapper = mapper::from_cartridge(test_cartridge(7, 0, 0x40000, 0x8000, 0, 0x2000)).unwrap();
+    assert_eq!(mapper.mirroring(), Mirroring::SingleScreenLower);
+    mapper.cpu_write(0x8000, 0x13);
+    assert_eq!(mapper.cpu_read(0x8000), Some(3));
+    assert_eq!(mapper.cpu_read(0xFFFF), Some(3));
+    assert_eq!(mapper.mirroring(), Mirroring::SingleScreenUpper);
+}
+
+#[test]
+fn test_unsupported_mapper() {
+    let result = mapper::from_cartridge(test_cartridge(99, 0, 0x8000, 0x8000, 0, 0x2000));
+    assert!(matches!(result, Err(CartridgeError::UnsupportedMapper(99))));
+}
+
+#[test]
+fn test_cartridge_on_nes_bus() {
+    let mut bus = NesBus::new();
+    bus.insert_cartridge(mapper::from_cartridge(test_cartridge(2, 1, 0x10000, 0x4000, 0, 0x2000)).unwrap());
+    assert_eq!(bus.read(0xC000), 3);
+    bus.write(0x8000, 2);
+    assert_eq!(bus.read(0x8000), 2);
+    // nothing is mapped at $5000, the data bus keeps the last value
+    assert_eq!(bus.read(0x5000), 2);
+}
+
+/***
+ * Helper method for loading an MMC1 register through the serial port.
+ */
+fn mmc1_write(mapper: &mut Box<dyn mapper::Mapper>, addr: u16, value: u8) {
+    for bit in 0..5 {
+        mapper.cpu_write(addr, (value >> bit) & 0x01);
+        mapper.cpu_clock(2);
+    }
+}
+
+#[test]
+fn test_mmc1_prg_modes() {
+    let mut mapper = mapper::from_cartridge(test_cartridge(1, 0, 0x40000, 0x4000, 0, 0x2000)).unwrap();
+    // power on: last bank fixed at $C000
+    assert_eq!(mapper.cpu_read(0xC000), Some(15));
+    mmc1_write(&mut mapper, 0xE000, 3);
+    assert_eq!(mapper.cpu_read(0x8000), Some(3));
+    // mode 2: first bank fixed at $8000
+    mmc1_write(&mut mapper, 0x8000, 0x0B);
+    assert_eq!(mapper.cpu_read(0x8000), Some(0));
+    assert_eq!(mapper.cpu_read(0xC000), Some(3));
+    assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+    // 32 KiB mode ignores the low bank bit
+    mmc1_write(&mut mapper, 0x8000, 0x02);
+    assert_eq!(mapper.cpu_read(0x8000), Some(2));
+    assert_eq!(mapper.cpu_read(0xC000), Some(3));
+    assert_eq!(mapper.mirroring(), Mirroring::Vertical);
+}
+
+#[test]
+fn test_mmc1_reset_and_consecutive_writes() {
+    let mut mapper = mapper::from_cartridge(test_cartridge(1, 0, 0x40000, 0x4000, 0, 0x2000)).unwrap();
+    mmc1_write(&mut mapper, 0x8000, 0x02);
+    // a partial load is discarded by the reset bit, which also restores PRG mode 3
+    mapper.cpu_write(0xE000, 1);
+    mapper.cpu_clock(2);
+    mapper.cpu_write(0xE000, 0x80);
+    mapper.cpu_clock(2);
+    assert_eq!(mapper.cpu_read(0xC000), Some(15));
+    mmc1_write(&mut mapper, 0xE000, 5);
+    assert_eq!(mapper.cpu_read(0x8000), Some(5));
+
+    // the second write without a cycle in between is ignored
+    mapper.cpu_write(0x8000, 0x80);
+    mapper.cpu_write(0x8000, 0x01);
+    mapper.cpu_clock(6);
+    for _ in 0..4 {
+        mapper.cpu_write(0xE000, 0);
+        mapper.cpu_clock(2);
+    }
+    assert_eq!(mapper.cpu_read(0x8000), Some(5));
+    mapper.cpu_write(0xE000, 0);
+    mapper.cpu_clock(2);
+    assert_eq!(mapper.cpu_read(0x8000), Some(0));
+}
+
+#[test]
+fn test_mmc1_chr_modes() {
+    let mut mapper = mapper::from_cartridge(test_cartridge(1, 0, 0x8000, 0x4000, 0x20000, 0x1000)).unwrap();
+    // 8 KiB mode ignores the low bank bit
+    mmc1_write(&mut mapper, 0xA000, 5);
+    assert_eq!(mapper.ppu_read(0x0000), 4);
+    assert_eq!(mapper.ppu_read(0x1000), 5);
+    // 4 KiB mode
+    mmc1_write(&mut mapper, 0x8000, 0x1C);
+    mmc1_write(&mut mapper, 0xC000, 9);
+    assert_eq!(mapper.ppu_read(0x0000), 5);
+    assert_eq!(mapper.ppu_read(0x1000), 9);
+}
+
+#[test]
+fn test_mmc1_prg_ram() {
+    let mut cartridge = test_cartridge(1, 0, 0x80000, 0x4000, 0, 0x2000);
+    cartridge.prg_ram_size = 0x8000;
+    let mut mapper = mapper::from_cartridge(cartridge).unwrap();
+    mapper.cpu_write(0x6000, 0x11);
+    mapper.cpu_clock(2);
+    // SXROM: CHR bank 0 bits 2-3 select the PRG RAM bank, bit 4 the PRG ROM half
+    mmc1_write(&mut mapper, 0xA000, 0x14);
+    assert_eq!(mapper.cpu_read(0x6000), Some(0x00));
+    assert_eq!(mapper.cpu_read(0xC000), Some(31));
+    mapper.cpu_write(0x6000, 0x22);
+    mmc1_write(&mut mapper, 0xA000, 0x00);
+    assert_eq!(mapper.cpu_read(0x6000), Some(0x11));
+    assert_eq!(mapper.cpu_read(0xC000), Some(15));
+    // disable PRG RAM
+    mmc1_write(&mut mapper, 0xE000, 0x10);
+    assert_eq!(mapper.cpu_read(0x6000), None);
+    mapper.cpu_write(0x6000, 0x33);
+    mmc1_write(&mut mapper, 0xE000, 0x00);
+    assert_eq!(mapper.cpu_read(0x6000), Some(0x11));
+}
+
+#[test]
+fn test_mmc1_read_modify_write() {
+    let mut cartridge = test_cartridge(1, 0, 0x40000, 0x4000, 0, 0x2000);
+    // fixed bank at $C000 runs INC $8000 five times
+    for i in 0..5 {
+        cartridge.prg_rom[0x3C000 + i * 3 .. 0x3C000 + i * 3 + 3].copy_from_slice(&[0xee, 0x00, 0x80]);
+    }
+    cartridge.prg_rom[0x3FFFC] = 0x00;
+    cartridge.prg_rom[0x3FFFD] = 0xC0;
+    let mut cpu = CPU::with_bus(NesBus::new());
+    cpu.memory.insert_cartridge(mapper::from_cartridge(cartridge).unwrap());
+    cpu.reset();
+    for _ in 0..5 {
+        assert_eq!(cpu.step(), StepResult::Executed { cycles: 6 });
+    }
+    // each INC writes 0x00 then 0x01 on consecutive cycles, only the 0x00 reaches the
+    // shift register so the control register is loaded with zero
+    let mapper = &cpu.memory.mapper;
+    assert_eq!(mapper.mirroring(), Mirroring::SingleScreenLower);
+    assert_eq!(mapper.cpu_peek(0xC000), Some(1));
+}
+
+#[test]
+fn test_mmc3_banking() {
+    let mut mapper = mapper::from_cartridge(test_cartridge(4, 0, 0x20000, 0x2000, 0x40000, 0x400)).unwrap();
+    mapper.cpu_write(0x8000, 6);
+    mapper.cpu_write(0x8001, 3);
+    mapper.cpu_write(0x8000, 7);
+    mapper.cpu_write(0x8001, 4);
+    assert_eq!(mapper.cpu_read(0x8000), Some(3));
+    assert_eq!(mapper.cpu_read(0xA000), Some(4));
+    assert_eq!(mapper.cpu_read(0xC000), Some(14));
+    assert_eq!(mapper.cpu_read(0xE000), Some(15));
+    // PRG mode 1 swaps $8000 and $C000
+    mapper.cpu_write(0x8000, 0x40);
+    assert_eq!(mapper.cpu_read(0x8000), Some(14));
+    assert_eq!(mapper.cpu_read(0xC000), Some(3));
+
+    // R0 is a 2 KiB bank, R2 a 1 KiB bank
+    mapper.cpu_write(0x8000, 0);
+    mapper.cpu_write(0x8001, 9);
+    mapper.cpu_write(0x8000, 2);
+    mapper.cpu_write(0x8001, 20);
+    assert_eq!(mapper.ppu_read(0x0000), 8);
+    assert_eq!(mapper.ppu_read(0x0400), 9);
+    assert_eq!(mapper.ppu_read(0x1000), 20);
+    // CHR inversion
+    mapper.cpu_write(0x8000, 0x80);
+    assert_eq!(mapper.ppu_read(0x0000), 20);
+    assert_eq!(mapper.ppu_read(0x1400), 9);
+
+    mapper.cpu_write(0xA000, 1);
+    assert_eq!(mapper.mirroring(), Mirroring::Horizontal);
+    // PRG RAM write protect
+    mapper.cpu_write(0x6000, 0x11);
+    mapper.cpu_write(0xA001, 0xC0);
+    mapper.cpu_write(0x6000, 0x22);
+    assert_eq!(mapper.cpu_read(0x6000), Some(0x11));
+    mapper.cpu_write(0xA001, 0x00);
+    assert_eq!(mapper.cpu_read(0x6000), None);
+}
+
+/***
+ * Helper method for feeding the mapper the A12 pattern of one rendered scanline,
+ * background fetches from $0000 and sprite fetches from $1000.
+ */
+fn mmc3_scanline(mapper: &mut Box<dyn mapper::Mapper>, scanline: u64) {
+    let start = scanline * 341;
+    for dot in 0..256 {
+        mapper.ppu_address(if dot % 2 == 0 { 0x2000 } else { 0x0000 }, start + dot);
+    }
+    // sprite fetches toggle A12 with short low periods in between
+    for sprite in 0..8 {
+        let dot = start + 257 + sprite * 8;
+        mapper.ppu_address(0x2000, dot);
+        mapper.ppu_address(0x1000, dot + 4);
+    }
+    for dot in 321..341 {
+        mapper.ppu_address(0x0000, start + dot);
+    }
+}
+
+#[test]
+fn test_mmc3_scanline_irq() {
+    let mut mapper = mapper::from_cartridge(test_cartridge(4, 0, 0x20000, 0x2000, 0x40000, 0x400)).unwrap();
+    mapper.cpu_write(0xC000, 2);
+    mapper.cpu_write(0xC001, 0);
+    mapper.cpu_write(0xE001, 0);
+    // reload to 2, then 1, then 0 triggers
+    mmc3_scanline(&mut mapper, 0);
+    mmc3_scanline(&mut mapper, 1);
+    assert!(!mapper.irq());
+    mmc3_scanline(&mut mapper, 2);
+    assert!(mapper.irq());
+    // acknowledge
+    mapper.cpu_write(0xE000, 0);
+    assert!(!mapper.irq());
+    mapper.cpu_write(0xE001, 0);
+    mmc3_scanline(&mut mapper, 3);
+    mmc3_scanline(&mut mapper, 4);
+    assert!(!mapper.irq());
+    mmc3_scanline(&mut mapper, 5);
+    assert!(mapper.irq());
+}
+
+#[test]
+fn test_mmc3_revisions() {
+    // with a zero latch the Sharp chip fires on every scanline
+    let mut sharp = mapper::from_cartridge(test_cartridge(4, 0, 0x20000, 0x2000, 0x40000, 0x400)).unwrap();
+    // submapper 4 is the NEC MMC3A, which only fires after a $C001 reload
+    let mut nec = mapper::from_cartridge(test_cartridge(4, 4, 0x20000, 0x2000, 0x40000, 0x400)).unwrap();
+    for mapper in [&mut sharp, &mut nec] {
+        mapper.cpu_write(0xC000, 0);
+        mapper.cpu_write(0xC001, 0);
+        mapper.cpu_write(0xE001, 0);
+        mmc3_scanline(mapper, 0);
+        assert!(mapper.irq());
+        mapper.cpu_write(0xE000, 0);
+        mapper.cpu_write(0xE001, 0);
+        mmc3_scanline(mapper, 1);
+    }
+    assert!(sharp.irq());
+    assert!(!nec.irq());
+}
+
+#[test]
+fn test_mmc3_irq_reaches_cpu() {
+    let mut cartridge = test_cartridge(4, 0, 0x8000, 0x2000, 0x2000, 0x400);
+    // NOPs at $E000, reset vector $E000, IRQ vector $E100
+    cartridge.prg_rom[0x6000..0x6010].fill(0xea);
+    cartridge.prg_rom[0x7FFC..0x8000].copy_from_slice(&[0x00, 0xE0, 0x00, 0xE1]);
+    let mut cpu = CPU::with_bus(NesBus::new());
+    cpu.memory.insert_cartridge(mapper::from_cartridge(cartridge).unwrap());
+    cpu.reset();
+    cpu.status = 0;
+    cpu.memory.write(0xC000, 0);
+    cpu.memory.write(0xC001, 0);
+    cpu.memory.write(0xE001, 0);
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+    mmc3_scanline(&mut cpu.memory.mapper, 0);
+    cpu.step();
+    assert!(matches!(cpu.step(), StepResult::Interrupt { .. }));
+    assert_eq!(cpu.program_counter, 0xE100);
+}
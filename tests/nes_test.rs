@@ -0,0 +1,106 @@
+use nesguin::cartridge::{Cartridge, ConsoleType, Mirroring, RomFormat, Timing};
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::StopReason;
+use nesguin::nes::{Nes, Region};
+
+/***
+ * Helper method for an NROM cartridge running the program at $8000,
+ * the NMI handler at $9000 counts frames in $0010.
+ */
+fn test_cartridge(program: &[u8], timing: Timing) -> Cartridge {
+    let mut prg_rom = vec![0xEA; 0x8000];
+    prg_rom[..program.len()].copy_from_slice(program);
+    // INC $10, RTI
+    prg_rom[0x1000..0x1003].copy_from_slice(&[0xe6, 0x10, 0x40]);
+    prg_rom[0x7FFA..].copy_from_slice(&[0x00, 0x90, 0x00, 0x80, 0x00, 0x90]);
+    Cartridge {
+        format: RomFormat::INes,
+        mapper: 0,
+        submapper: 0,
+        mirroring: Mirroring::Vertical,
+        battery: false,
+        trainer: None,
+        prg_rom,
+        chr_rom: vec![0; 0x2000],
+        prg_ram_size: 0,
+        prg_nvram_size: 0,
+        chr_ram_size: 0,
+        chr_nvram_size: 0,
+        timing,
+        console_type: ConsoleType::NES
+    }
+}
+
+/* JMP $8000 */
+const IDLE_LOOP:[u8; 3] = [0x4c, 0x00, 0x80];
+/* LDA #$80, STA $2000, JMP $8005 */
+const NMI_LOOP:[u8; 8] = [0xa9, 0x80, 0x8d, 0x00, 0x20, 0x4c, 0x05, 0x80];
+
+#[test]
+fn test_ntsc_frame() {
+    let mut nes = Nes::with_cartridge(test_cartridge(&IDLE_LOOP, Timing::NTSC)).unwrap();
+    assert_eq!(nes.region(), Region::NTSC);
+    assert_eq!(nes.run_frame(), StopReason::ConditionMet);
+    let start = nes.cpu.cycles;
+    nes.run_frame();
+    // 262 lines of 341 dots at 3 dots per cycle, less one dot on odd frames
+    assert!((nes.cpu.cycles - start).abs_diff(29781) <= 3);
+    assert_eq!(nes.bus().ppu.cycles * 4, nes.bus().master_clock());
+}
+
+#[test]
+fn test_pal_frame() {
+    let mut nes = Nes::with_cartridge(test_cartridge(&IDLE_LOOP, Timing::PAL)).unwrap();
+    assert_eq!(nes.region(), Region::PAL);
+    nes.run_cycles(1000);
+    // 3.2 dots per CPU cycle
+    assert_eq!(nes.bus().ppu.cycles, nes.cpu.cycles * 16 / 5);
+    nes.run_frame();
+    let start = nes.cpu.cycles;
+    nes.run_frame();
+    // 312 lines and no skipped dot
+    assert!((nes.cpu.cycles - start).abs_diff(33248) <= 3);
+    assert_eq!(nes.bus().ppu.scanline, 241);
+}
+
+#[test]
+fn test_run_cycles() {
+    let mut nes = Nes::with_cartridge(test_cartridge(&IDLE_LOOP, Timing::NTSC)).unwrap();
+    let start = nes.cpu.cycles;
+    assert_eq!(nes.run_cycles(100), StopReason::ConditionMet);
+    // JMP takes 3 cycles, the last instruction is finished
+    assert_eq!(nes.cpu.cycles - start, 102);
+    assert_eq!(nes.bus().ppu.cycles, nes.cpu.cycles * 3);
+}
+
+#[test]
+fn test_vblank_nmi_each_frame() {
+    let mut nes = Nes::with_cartridge(test_cartridge(&NMI_LOOP, Timing::NTSC)).unwrap();
+    for _ in 0..3 {
+        nes.run_frame();
+    }
+    nes.run_cycles(100);
+    assert_eq!(nes.bus().peek(0x0010), 3);
+}
+
+#[test]
+fn test_audio_per_frame() {
+    // LDA #$01, STA $4015, LDA #$BF, STA $4000, LDA #$FD, STA $4002, LDA #$08, STA $4003, JMP $8014
+    let program = [0xa9, 0x01, 0x8d, 0x15, 0x40, 0xa9, 0xbf, 0x8d, 0x00, 0x40, 0xa9, 0xfd, 0x8d, 0x02, 0x40,
+        0xa9, 0x08, 0x8d, 0x03, 0x40, 0x4c, 0x14, 0x80];
+    let mut nes = Nes::with_cartridge(test_cartridge(&program, Timing::NTSC)).unwrap();
+    nes.bus_mut().apu.resampler.set_sample_rate(48000.0);
+    nes.run_frame();
+    nes.take_audio_samples();
+    nes.run_frame();
+    let samples = nes.take_audio_samples();
+    assert!(samples.len().abs_diff(800) <= 2);
+    assert!(samples.iter().any(|sample| sample.abs() > 0.05));
+}
+
+#[test]
+fn test_jammed_cpu_stops_frame() {
+    let mut nes = Nes::with_cartridge(test_cartridge(&[0x02], Timing::NTSC)).unwrap();
+    assert_eq!(nes.run_frame(), StopReason::Jammed);
+    assert_eq!(nes.run_cycles(10), StopReason::Jammed);
+}
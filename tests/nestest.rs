@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::PathBuf;
+
+use nesguin::cartridge::Cartridge;
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::StepResult;
+use nesguin::nes::Nes;
+
+/* Lines of the golden log shown around a divergence */
+const CONTEXT_LINES:usize = 5;
+
+/***
+ * nestest.nes and its Nintendulator log are not redistributed with the
+ * sources, put them in tests/roms or point NESTEST_DIR at them and run
+ * the ignored tests.
+ */
+fn nestest_dir() -> PathBuf {
+    std::env::var("NESTEST_DIR").map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms"))
+}
+
+#[test]
+#[ignore = "needs nestest.nes and nestest.log, see nestest_dir"]
+fn test_nestest_golden_log() {
+    let dir = nestest_dir();
+    let rom = fs::read(dir.join("nestest.nes"))
+        .unwrap_or_else(|error| panic!("cannot read nestest.nes in {}: {}", dir.display(), error));
+    let log = fs::read_to_string(dir.join("nestest.log"))
+        .unwrap_or_else(|error| panic!("cannot read nestest.log in {}: {}", dir.display(), error));
+    let mut nes = Nes::with_cartridge(Cartridge::from_bytes(&rom).unwrap()).unwrap();
+    // automation mode starts at $C000 instead of the reset vector
+    nes.cpu.program_counter = 0xC000;
+    let expected: Vec<&str> = log.lines().map(|line| line.trim_end()).collect();
+    let mut actual: Vec<String> = Vec::new();
+    for (number, expected_line) in expected.iter().enumerate() {
+        let line = nes.cpu.trace_event().nintendulator(nes.bus(), nes.bus().ppu.scanline, nes.bus().ppu.dot);
+        if line != *expected_line {
+            let start = number.saturating_sub(CONTEXT_LINES);
+            let mut report = format!("nestest diverges at line {}\n", number + 1);
+            for (offset, previous) in actual[start..].iter().enumerate() {
+                report.push_str(&format!("  {:5}  {}\n", start + offset + 1, previous));
+            }
+            report.push_str(&format!("expected {}\n", expected_line));
+            report.push_str(&format!("actual   {}\n", line));
+            panic!("{}", report);
+        }
+        actual.push(line);
+        if let StepResult::Jammed | StepResult::IllegalOpcode(_) = nes.cpu.step() {
+            panic!("CPU stopped at line {}: {}", number + 1, expected_line);
+        }
+    }
+    // nestest leaves the number of the first failed official and unofficial test in $02 and $03
+    assert_eq!(nes.bus().peek(0x0002), 0x00);
+    assert_eq!(nes.bus().peek(0x0003), 0x00);
+}
@@ -0,0 +1,38 @@
+use nesguin::emu6502::op_codes::{AddressingMode, OpCodeMap, OP_CODES_TABLE};
+
+#[test]
+fn table_is_indexed_by_opcode() {
+    for (index, op_code) in OP_CODES_TABLE.iter().enumerate() {
+        assert_eq!(op_code.code as usize, index);
+    }
+}
+
+#[test]
+fn size_matches_addressing_mode() {
+    for op_code in OP_CODES_TABLE.iter() {
+        let expected = match op_code.mode {
+            AddressingMode::NoneAddressing | AddressingMode::Accumulator => 1,
+            AddressingMode::Immediate
+            | AddressingMode::Relative
+            | AddressingMode::ZeroPage
+            | AddressingMode::ZeroPage_X
+            | AddressingMode::ZeroPage_Y
+            | AddressingMode::Indirect_X
+            | AddressingMode::Indirect_Y => 2,
+            AddressingMode::Absolute
+            | AddressingMode::Absolute_X
+            | AddressingMode::Absolute_Y
+            | AddressingMode::Indirect => 3,
+        };
+        assert_eq!(op_code.size, expected, "size of opcode {:#04x} ({})", op_code.code, op_code.name);
+    }
+}
+
+#[test]
+fn lookup_returns_table_entry() {
+    let op_codes = OpCodeMap::new();
+    let op_code = op_codes.get_op_code(&0xb1);
+    assert_eq!(op_code.name, "LDA");
+    assert_eq!(op_code.mode, AddressingMode::Indirect_Y);
+    assert_eq!(op_code.cycles, 5);
+}
@@ -0,0 +1,369 @@
+use nesguin::bus::NesBus;
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::{CPU, StopCondition, StopReason, NMI_VECTOR};
+use nesguin::cartridge::{Cartridge, ConsoleType, Mirroring, RomFormat, Timing};
+use nesguin::mapper;
+use nesguin::mapper::ram_cartridge::RamCartridge;
+use nesguin::ppu::{PPU, VBLANK_SCANLINE};
+
+#[test]
+fn test_loopy_registers() {
+    let mut bus = NesBus::new();
+    // nametable select
+    bus.write(0x2000, 0x03);
+    assert_eq!(bus.ppu.temp_vram_addr(), 0x0C00);
+    // scroll X = 0x7D, Y = 0x5E
+    bus.write(0x2005, 0x7D);
+    assert_eq!(bus.ppu.temp_vram_addr(), 0x0C0F);
+    assert_eq!(bus.ppu.fine_x(), 5);
+    assert!(bus.ppu.write_toggle());
+    bus.write(0x2005, 0x5E);
+    assert_eq!(bus.ppu.temp_vram_addr(), 0x6D6F);
+    assert!(!bus.ppu.write_toggle());
+    // PPUADDR copies t to v on the second write
+    bus.write(0x2006, 0x3D);
+    assert_eq!(bus.ppu.temp_vram_addr(), 0x3D6F);
+    bus.write(0x2006, 0xF0);
+    assert_eq!(bus.ppu.temp_vram_addr(), 0x3DF0);
+    assert_eq!(bus.ppu.vram_addr(), 0x3DF0);
+    // reading PPUSTATUS resets the write toggle
+    bus.write(0x2005, 0x00);
+    bus.read(0x2002);
+    assert!(!bus.ppu.write_toggle());
+}
+
+#[test]
+fn test_ppudata_reads() {
+    let mut bus = NesBus::new();
+    bus.write(0x2006, 0x24);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0x11);
+    bus.write(0x2007, 0x22);
+    bus.write(0x2006, 0x24);
+    bus.write(0x2006, 0x00);
+    // the first read returns the stale buffer
+    bus.read(0x2007);
+    assert_eq!(bus.read(0x2007), 0x11);
+    assert_eq!(bus.read(0x2007), 0x22);
+    // increment by 32
+    bus.write(0x2000, 0x04);
+    bus.write(0x2006, 0x20);
+    bus.write(0x2006, 0x00);
+    bus.read(0x2007);
+    assert_eq!(bus.ppu.vram_addr(), 0x2020);
+    // horizontal mirroring without a cartridge, $2000 and $2400 share memory
+    bus.write(0x2000, 0x00);
+    bus.write(0x2006, 0x20);
+    bus.write(0x2006, 0x01);
+    bus.read(0x2007);
+    assert_eq!(bus.read(0x2007), 0x22);
+}
+
+#[test]
+fn test_palette_access() {
+    let mut bus = NesBus::new();
+    bus.write(0x2006, 0x2F);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0x55);
+    bus.write(0x2006, 0x3F);
+    bus.write(0x2006, 0x10);
+    bus.write(0x2007, 0x2C);
+    // palette reads are not buffered, $3F10 mirrors $3F00
+    bus.write(0x2006, 0x3F);
+    bus.write(0x2006, 0x00);
+    assert_eq!(bus.read(0x2007) & 0x3F, 0x2C);
+    // the buffer holds the nametable byte under the palette
+    bus.write(0x2006, 0x3F);
+    bus.write(0x2006, 0x00);
+    bus.read(0x2007);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2006, 0x00);
+    assert_eq!(bus.read(0x2007), 0x55);
+    // grayscale
+    bus.write(0x2001, 0x01);
+    bus.write(0x2006, 0x3F);
+    bus.write(0x2006, 0x00);
+    assert_eq!(bus.read(0x2007) & 0x3F, 0x20);
+}
+
+#[test]
+fn test_register_mirrors_and_oam() {
+    let mut bus = NesBus::new();
+    bus.write(0x3FFB, 0x10);
+    bus.write(0x200C, 0xAB);
+    assert_eq!(bus.ppu.oam[0x10], 0xAB);
+    assert_eq!(bus.ppu.oam_addr, 0x11);
+    // attribute bits 2-4 read back as zero
+    bus.write(0x2003, 0x12);
+    bus.write(0x2004, 0xFF);
+    bus.write(0x2003, 0x12);
+    assert_eq!(bus.read(0x2004), 0xE3);
+    assert_eq!(bus.ppu.oam_addr, 0x12);
+}
+
+#[test]
+fn test_vblank_flag() {
+    let mut ppu = PPU::new();
+    while !(ppu.scanline == VBLANK_SCANLINE && ppu.dot == 2) {
+        ppu.clock(&mut RamCartridge::new());
+    }
+    assert_eq!(ppu.status & 0x80, 0x80);
+    assert!(!ppu.nmi());
+    ppu.ctrl = 0x80;
+    assert!(ppu.nmi());
+
+    let mut bus = NesBus::new();
+    bus.ppu = ppu;
+    assert_eq!(bus.read(0x2002) & 0x80, 0x80);
+    assert_eq!(bus.read(0x2002) & 0x80, 0x00);
+    assert!(!bus.nmi());
+    // cleared on the pre-render line
+    bus.ppu.status = 0xE0;
+    while bus.ppu.scanline != 0 {
+        bus.ppu.clock(bus.mapper.as_mut());
+    }
+    assert_eq!(bus.ppu.status, 0);
+}
+
+#[test]
+fn test_vblank_nmi_reaches_cpu() {
+    let mut cpu = CPU::with_bus(NesBus::new());
+    // LDA #$80, STA $2000, JMP $8005
+    cpu.load_program(vec![0xa9, 0x80, 0x8d, 0x00, 0x20, 0x4c, 0x05, 0x80]);
+    cpu.memory.write_word(NMI_VECTOR, 0x9000);
+    cpu.reset();
+    let start_cycles = cpu.cycles;
+    assert_eq!(cpu.run_until(StopCondition::ProgramCounter(0x9000)), StopReason::ConditionMet);
+    // vblank starts on dot 1 of scanline 241, three dots per CPU cycle
+    let vblank_cycle = (241 * 341 + 1) / 3;
+    let elapsed = cpu.cycles - start_cycles;
+    assert!(elapsed >= vblank_cycle && elapsed < vblank_cycle + 20, "NMI after {} cycles", elapsed);
+}
+
+/***
+ * Helper method for writing PPU memory through PPUADDR and PPUDATA.
+ */
+fn write_ppu_memory(bus: &mut NesBus, addr: u16, data: &[u8]) {
+    bus.write(0x2006, (addr >> 8) as u8);
+    bus.write(0x2006, addr as u8);
+    for byte in data {
+        bus.write(0x2007, *byte);
+    }
+}
+
+/***
+ * Helper method for clocking the PPU until the next vblank starts.
+ */
+fn run_to_vblank(bus: &mut NesBus) {
+    bus.ppu.frame_complete = false;
+    while !bus.ppu.frame_complete {
+        bus.ppu.clock(bus.mapper.as_mut());
+    }
+}
+
+/***
+ * Helper method for a bus in vblank with a solid tile 1 (color 1) and tile 2 (color 3) in CHR RAM.
+ */
+fn setup_rendering() -> NesBus {
+    let mut bus = NesBus::new();
+    run_to_vblank(&mut bus);
+    write_ppu_memory(&mut bus, 0x0010, &[0xFF; 8]);
+    write_ppu_memory(&mut bus, 0x0020, &[0xFF; 16]);
+    write_ppu_memory(&mut bus, 0x3F00, &[0x0F, 0x21, 0x22, 0x23]);
+    write_ppu_memory(&mut bus, 0x3F10, &[0x0F, 0x16, 0x17, 0x18, 0x0F, 0x26, 0x27, 0x28]);
+    bus
+}
+
+/***
+ * Helper method for resetting scroll and enabling rendering, as done at the end of vblank.
+ */
+fn start_rendering(bus: &mut NesBus, ctrl: u8, scroll_x: u8) {
+    bus.write(0x2000, ctrl);
+    bus.write(0x2005, scroll_x);
+    bus.write(0x2005, 0);
+    bus.write(0x2001, 0x1E);
+}
+
+#[test]
+fn test_background_rendering() {
+    let mut bus = setup_rendering();
+    // tile 1 at column 0 row 0, tile 2 at column 3 row 2
+    write_ppu_memory(&mut bus, 0x2000, &[0x01]);
+    write_ppu_memory(&mut bus, 0x2043, &[0x02]);
+    start_rendering(&mut bus, 0x00, 0);
+    run_to_vblank(&mut bus);
+    assert_eq!(bus.ppu.pixel(0, 0), 0x21);
+    assert_eq!(bus.ppu.pixel(7, 7), 0x21);
+    assert_eq!(bus.ppu.pixel(8, 0), 0x0F);
+    assert_eq!(bus.ppu.pixel(0, 8), 0x0F);
+    assert_eq!(bus.ppu.pixel(24, 16), 0x23);
+    assert_eq!(bus.ppu.pixel(31, 23), 0x23);
+    assert_eq!(bus.ppu.pixel(32, 23), 0x0F);
+    assert_eq!(bus.ppu.frame_buffer.len(), 256 * 240);
+}
+
+#[test]
+fn test_fine_x_scroll_and_clipping() {
+    let mut bus = setup_rendering();
+    write_ppu_memory(&mut bus, 0x2000, &[0x01, 0x00, 0x01]);
+    // scroll 4 pixels right
+    start_rendering(&mut bus, 0x00, 4);
+    run_to_vblank(&mut bus);
+    assert_eq!(bus.ppu.pixel(3, 0), 0x21);
+    assert_eq!(bus.ppu.pixel(4, 0), 0x0F);
+    assert_eq!(bus.ppu.pixel(12, 0), 0x21);
+    // hide the background in the leftmost 8 pixels
+    bus.write(0x2001, 0x1C);
+    run_to_vblank(&mut bus);
+    assert_eq!(bus.ppu.pixel(3, 0), 0x0F);
+    assert_eq!(bus.ppu.pixel(12, 0), 0x21);
+}
+
+#[test]
+fn test_mid_frame_scroll_change() {
+    let mut bus = setup_rendering();
+    // tile 1 at column 1 of rows 12 and 13, scanlines 96-111
+    write_ppu_memory(&mut bus, 0x2181, &[0x01]);
+    write_ppu_memory(&mut bus, 0x21A1, &[0x01]);
+    start_rendering(&mut bus, 0x00, 0);
+    run_to_vblank(&mut bus);
+    // change horizontal scroll while scanline 100 is drawn, it applies from the next line
+    while bus.ppu.scanline != 100 {
+        bus.ppu.clock(bus.mapper.as_mut());
+    }
+    bus.write(0x2005, 8);
+    bus.write(0x2005, 0);
+    run_to_vblank(&mut bus);
+    assert_eq!(bus.ppu.pixel(0, 100), 0x0F);
+    assert_eq!(bus.ppu.pixel(8, 100), 0x21);
+    assert_eq!(bus.ppu.pixel(0, 101), 0x21);
+    assert_eq!(bus.ppu.pixel(8, 101), 0x0F);
+}
+
+#[test]
+fn test_sprites() {
+    let mut bus = setup_rendering();
+    // background tile 1 at column 2 row 1
+    write_ppu_memory(&mut bus, 0x2022, &[0x01]);
+    // sprite 0 on top at (20, 10), sprite 1 behind the background at (16, 12) with palette 1
+    bus.ppu.oam[0..8].copy_from_slice(&[9, 0x02, 0x00, 20, 11, 0x02, 0x21, 14]);
+    start_rendering(&mut bus, 0x00, 0);
+    run_to_vblank(&mut bus);
+    assert_eq!(bus.ppu.pixel(20, 10), 0x18);
+    assert_eq!(bus.ppu.pixel(19, 10), 0x21);
+    // behind an opaque background pixel, in front of the backdrop
+    assert_eq!(bus.ppu.pixel(16, 14), 0x21);
+    assert_eq!(bus.ppu.pixel(15, 14), 0x28);
+    // sprite 0 hit is set by the overlap and cleared on the pre-render line
+    assert_ne!(bus.ppu.status & 0x40, 0);
+    bus.ppu.oam[3] = 100;
+    while bus.ppu.scanline != 0 {
+        bus.ppu.clock(bus.mapper.as_mut());
+    }
+    run_to_vblank(&mut bus);
+    assert_eq!(bus.ppu.status & 0x40, 0);
+}
+
+#[test]
+fn test_sprite_limit_and_overflow() {
+    let mut bus = setup_rendering();
+    // nine sprites on line 50
+    for n in 0..9 {
+        bus.ppu.oam[n * 4 .. n * 4 + 4].copy_from_slice(&[49, 0x01, 0x00, (n * 10) as u8]);
+    }
+    for n in 9..64 {
+        bus.ppu.oam[n * 4] = 0xF0;
+    }
+    start_rendering(&mut bus, 0x00, 0);
+    run_to_vblank(&mut bus);
+    assert_eq!(bus.ppu.pixel(70, 50), 0x16);
+    assert_eq!(bus.ppu.pixel(80, 50), 0x0F);
+    assert_ne!(bus.ppu.status & 0x20, 0);
+
+    // eight sprites on a line do not overflow, but the buggy scan reads a tile
+    // number as the Y position of the next entries
+    for n in 8..64 {
+        bus.ppu.oam[n * 4 .. n * 4 + 4].copy_from_slice(&[0xF0, 0x00, 0x00, 0x00]);
+    }
+    bus.ppu.oam[9 * 4 + 1] = 49;
+    run_to_vblank(&mut bus);
+    assert_ne!(bus.ppu.status & 0x20, 0);
+    bus.ppu.oam[9 * 4 + 1] = 0x00;
+    run_to_vblank(&mut bus);
+    assert_eq!(bus.ppu.status & 0x20, 0);
+}
+
+#[test]
+fn test_8x16_sprites() {
+    let mut bus = setup_rendering();
+    // tile $02 bottom half is tile $03, which is empty
+    write_ppu_memory(&mut bus, 0x1010, &[0xFF; 16]);
+    bus.ppu.oam[0..4].copy_from_slice(&[29, 0x02, 0x00, 40]);
+    bus.ppu.oam[4..8].copy_from_slice(&[29, 0x01, 0x80, 60]);
+    for n in 2..64 {
+        bus.ppu.oam[n * 4] = 0xF0;
+    }
+    start_rendering(&mut bus, 0x20, 0);
+    run_to_vblank(&mut bus);
+    assert_eq!(bus.ppu.pixel(40, 30), 0x18);
+    assert_eq!(bus.ppu.pixel(40, 37), 0x18);
+    assert_eq!(bus.ppu.pixel(40, 38), 0x0F);
+    // odd tile numbers use $1000, vertical flip puts tile $01 of that table at the top
+    assert_eq!(bus.ppu.pixel(60, 30), 0x18);
+    assert_eq!(bus.ppu.pixel(60, 38), 0x0F);
+}
+
+#[test]
+fn test_odd_frame_skips_a_dot() {
+    let mut bus = setup_rendering();
+    start_rendering(&mut bus, 0x00, 0);
+    let mut frame_lengths = Vec::new();
+    for _ in 0..4 {
+        let start = bus.ppu.cycles;
+        run_to_vblank(&mut bus);
+        frame_lengths.push(bus.ppu.cycles - start);
+    }
+    assert!(frame_lengths.contains(&89341));
+    assert!(frame_lengths.contains(&89342));
+    // without rendering every frame is full length
+    bus.write(0x2001, 0x00);
+    run_to_vblank(&mut bus);
+    let start = bus.ppu.cycles;
+    run_to_vblank(&mut bus);
+    assert_eq!(bus.ppu.cycles - start, 89342);
+    run_to_vblank(&mut bus);
+}
+
+#[test]
+fn test_mmc3_irq_from_rendering() {
+    let cartridge = Cartridge {
+        format: RomFormat::INes,
+        mapper: 4,
+        submapper: 0,
+        mirroring: Mirroring::Vertical,
+        battery: false,
+        trainer: None,
+        prg_rom: vec![0; 0x8000],
+        chr_rom: vec![0; 0x2000],
+        prg_ram_size: 0x2000,
+        prg_nvram_size: 0,
+        chr_ram_size: 0,
+        chr_nvram_size: 0,
+        timing: Timing::NTSC,
+        console_type: ConsoleType::NES
+    };
+    let mut bus = NesBus::new();
+    bus.insert_cartridge(mapper::from_cartridge(cartridge).unwrap());
+    run_to_vblank(&mut bus);
+    bus.write(0xC000, 10);
+    bus.write(0xC001, 0);
+    bus.write(0xE001, 0);
+    // background from $0000, sprites from $1000
+    start_rendering(&mut bus, 0x08, 0);
+    while !bus.irq() {
+        bus.ppu.clock(bus.mapper.as_mut());
+    }
+    // reloaded on the pre-render line, counts down to zero on line 9
+    assert_eq!(bus.ppu.scanline, 9);
+    assert!(bus.ppu.dot > 256 && bus.ppu.dot < 270);
+}
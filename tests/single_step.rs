@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::{CPUVariant, CPU, StepResult};
+use nesguin::emu6502::op_codes::{Instruction, OpCodeMap};
+use serde::Deserialize;
+
+/***
+ * The SingleStepTests 6502 corpus (one 00.json-ff.json file per opcode) is
+ * not redistributed with the sources, put the v1 directory in
+ * tests/roms/6502 or point SINGLE_STEP_DIR at it and run the ignored tests.
+ */
+fn corpus_dir() -> PathBuf {
+    std::env::var("SINGLE_STEP_DIR").map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms").join("6502"))
+}
+
+/***
+ * CPU registers and the RAM contents a vector sets up or expects.
+ */
+#[derive(Deserialize)]
+struct State {
+    pc:u16,
+    s:u8,
+    a:u8,
+    x:u8,
+    y:u8,
+    p:u8,
+    ram:Vec<(u16, u8)>
+}
+
+/***
+ * One test vector. The bus activity is only used for the cycle count, the
+ * accesses are not compared cycle by cycle since the CPU does not make the
+ * dummy reads and writes of the hardware.
+ */
+#[derive(Deserialize)]
+struct Vector {
+    name:String,
+    initial:State,
+    #[serde(rename = "final")]
+    expected:State,
+    cycles:Vec<(u16, u8, String)>
+}
+
+/***
+ * Flat 64 KiB memory.
+ */
+struct FlatBus {
+    memory:Vec<u8>
+}
+
+impl Bus for FlatBus {
+    fn read(&mut self, addr:u16) -> u8 {
+        self.memory[addr as usize]
+    }
+
+    fn write(&mut self, addr:u16, data:u8) {
+        self.memory[addr as usize] = data;
+    }
+
+    fn peek(&self, addr:u16) -> u8 {
+        self.memory[addr as usize]
+    }
+}
+
+/***
+ * Runs one vector, returns a description of the first mismatch.
+ */
+fn run_vector(cpu: &mut CPU<FlatBus>, vector: &Vector) -> Result<(), String> {
+    let initial = &vector.initial;
+    cpu.program_counter = initial.pc;
+    cpu.stack_pointer = initial.s;
+    cpu.register_a = initial.a;
+    cpu.register_x = initial.x;
+    cpu.register_y = initial.y;
+    cpu.status = initial.p;
+    cpu.jammed = false;
+    for &(addr, data) in &initial.ram {
+        cpu.memory.memory[addr as usize] = data;
+    }
+    let cycles = match cpu.step() {
+        StepResult::Executed { cycles } | StepResult::Break { cycles } => cycles as usize,
+        result => return Err(format!("{}: stopped with {:?}", vector.name, result))
+    };
+
+    let expected = &vector.expected;
+    let registers = [
+        ("pc", cpu.program_counter, expected.pc),
+        ("s", cpu.stack_pointer as u16, expected.s as u16),
+        ("a", cpu.register_a as u16, expected.a as u16),
+        ("x", cpu.register_x as u16, expected.x as u16),
+        ("y", cpu.register_y as u16, expected.y as u16),
+        ("p", cpu.status as u16, expected.p as u16)
+    ];
+    for (name, actual, expected) in registers {
+        if actual != expected {
+            return Err(format!("{}: {} is {:X}, expected {:X}", vector.name, name, actual, expected));
+        }
+    }
+    for &(addr, data) in &expected.ram {
+        if cpu.memory.memory[addr as usize] != data {
+            return Err(format!("{}: ${:04X} is {:02X}, expected {:02X}", vector.name, addr, cpu.memory.memory[addr as usize], data));
+        }
+    }
+    if cycles != vector.cycles.len() {
+        return Err(format!("{}: took {} cycles, expected {}", vector.name, cycles, vector.cycles.len()));
+    }
+    Ok(())
+}
+
+/***
+ * Opcode results: passed and failed vector counts and the first failure.
+ */
+struct OpcodeReport {
+    opcode:u8,
+    passed:usize,
+    failed:usize,
+    first_failure:Option<String>
+}
+
+/***
+ * Runs every vector of an opcode, a file that cannot be read or parsed is
+ * reported as a failure of the opcode.
+ */
+fn run_opcode_file(path: &Path, opcode:u8) -> OpcodeReport {
+    let mut report = OpcodeReport { opcode, passed: 0, failed: 0, first_failure: None };
+    let vectors = fs::read_to_string(path).map_err(|error| error.to_string())
+        .and_then(|text| serde_json::from_str::<Vec<Vector>>(&text).map_err(|error| error.to_string()));
+    let vectors = match vectors {
+        Ok(vectors) => vectors,
+        Err(error) => {
+            report.failed = 1;
+            report.first_failure = Some(format!("{}: {}", path.display(), error));
+            return report;
+        }
+    };
+    let mut cpu = CPU::with_bus(FlatBus { memory: vec![0; 0x10000] });
+    // the corpus was recorded on a NMOS 6502 with decimal mode
+    cpu.variant = CPUVariant::NMOS6502;
+    for vector in &vectors {
+        match run_vector(&mut cpu, vector) {
+            Ok(()) => report.passed += 1,
+            Err(failure) => {
+                report.failed += 1;
+                report.first_failure.get_or_insert(failure);
+            }
+        }
+    }
+    report
+}
+
+#[test]
+#[ignore = "needs the SingleStepTests corpus, see corpus_dir"]
+fn test_single_step_corpus() {
+    let dir = corpus_dir();
+    assert!(dir.is_dir(), "SingleStepTests corpus not found in {}", dir.display());
+    let op_codes = OpCodeMap::new();
+    let mut reports = Vec::new();
+    for opcode in 0..=0xFF {
+        let op_code = op_codes.get_op_code(&opcode);
+        let path = dir.join(format!("{:02x}.json", opcode));
+        // a jammed CPU only repeats bus cycles, nothing to compare
+        if op_code.instruction == Instruction::JAM {
+            continue;
+        }
+        reports.push(run_opcode_file(&path, opcode));
+    }
+
+    let mut summary = String::from("opcode  name   passed  failed  first failure\n");
+    for report in &reports {
+        summary.push_str(&format!("  {:02X}    {:<5} {:>7} {:>7}  {}\n",
+            report.opcode, op_codes.get_op_code(&report.opcode).name, report.passed, report.failed,
+            report.first_failure.as_deref().unwrap_or("")));
+    }
+    let failed: usize = reports.iter().map(|report| report.failed).sum();
+    eprintln!("{}", summary);
+    assert_eq!(failed, 0, "{} vectors failed\n{}", failed, summary);
+}
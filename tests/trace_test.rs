@@ -0,0 +1,122 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use nesguin::emu6502::bus::Bus;
+use nesguin::emu6502::cpu::{CPU, StepResult};
+use nesguin::emu6502::ram::RAM;
+use nesguin::emu6502::trace::{Diagnostic, TraceEvent, Tracer};
+
+/***
+ * Tracer collecting everything it is sent into shared lists.
+ */
+#[derive(Default)]
+struct Recorder {
+    events:Rc<RefCell<Vec<TraceEvent>>>,
+    diagnostics:Rc<RefCell<Vec<Diagnostic>>>
+}
+
+impl Tracer for Recorder {
+    fn instruction(&mut self, event: &TraceEvent) {
+        self.events.borrow_mut().push(*event);
+    }
+
+    fn diagnostic(&mut self, diagnostic: &Diagnostic) {
+        self.diagnostics.borrow_mut().push(*diagnostic);
+    }
+}
+
+#[test]
+fn test_instruction_events() {
+    // LDA #$C0, TAX, STA $0200
+    let mut cpu = CPU::new();
+    cpu.load_program(vec![0xa9, 0xc0, 0xaa, 0x8d, 0x00, 0x02]);
+    cpu.reset();
+    let recorder = Recorder::default();
+    let events = recorder.events.clone();
+    cpu.tracer = Some(Box::new(recorder));
+    for _ in 0..3 {
+        cpu.step();
+    }
+    let events = events.borrow();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].program_counter, 0x8000);
+    assert_eq!(events[0].operands(), &[0xc0]);
+    assert_eq!(events[1].opcode, 0xaa);
+    assert_eq!(events[1].operands(), &[] as &[u8]);
+    // registers and cycles are captured before the instruction runs
+    assert_eq!(events[1].register_a, 0xc0);
+    assert_eq!(events[1].register_x, 0x00);
+    assert_eq!(events[2].cycles, events[1].cycles + 2);
+    assert_eq!(events[2].to_string(), format!("8003  8D 00 02  STA $0200     A:C0 X:C0 Y:00 P:A4 SP:FD CYC:{}", events[2].cycles));
+}
+
+#[test]
+fn test_closure_tracer() {
+    let count = Rc::new(RefCell::new(0));
+    let counter = count.clone();
+    let mut cpu = CPU::new();
+    cpu.load_program(vec![0xe8, 0xe8, 0xe8]);
+    cpu.reset();
+    cpu.tracer = Some(Box::new(move |_event: &TraceEvent| *counter.borrow_mut() += 1));
+    for _ in 0..3 {
+        cpu.step();
+    }
+    assert_eq!(*count.borrow(), 3);
+}
+
+#[test]
+fn test_diagnostics() {
+    let recorder = Recorder::default();
+    let diagnostics = recorder.diagnostics.clone();
+    let mut cpu = CPU::new();
+    cpu.load_program(vec![0xa7, 0x00, 0x02]);
+    cpu.reset();
+    cpu.unofficial_opcodes = false;
+    cpu.tracer = Some(Box::new(recorder));
+    assert_eq!(cpu.step(), StepResult::IllegalOpcode(0xa7));
+    cpu.unofficial_opcodes = true;
+    cpu.program_counter = 0x8002;
+    assert_eq!(cpu.step(), StepResult::Jammed);
+    assert_eq!(*diagnostics.borrow(), vec![
+        Diagnostic::IllegalOpcode { program_counter: 0x8000, opcode: 0xa7 },
+        Diagnostic::Jammed { program_counter: 0x8002, opcode: 0x02 }
+    ]);
+}
+
+#[test]
+fn test_nintendulator_line() {
+    // LDY #$10, LDA ($80),Y
+    let mut cpu = CPU::new();
+    cpu.load_program(vec![0xa0, 0x10, 0xb1, 0x80]);
+    cpu.reset();
+    cpu.memory.write_word(0x0080, 0x0200);
+    cpu.memory.write(0x0210, 0x5a);
+    cpu.step();
+    let recorder = Recorder::default();
+    let events = recorder.events.clone();
+    let event = cpu.trace_event();
+    cpu.tracer = Some(Box::new(recorder));
+    cpu.step();
+    // the event matches the one the tracer is sent
+    assert_eq!(events.borrow()[0], event);
+    assert_eq!(event.nintendulator(&cpu.memory, 0, 21),
+        format!("8002  B1 80     LDA ($80),Y = 0200 @ 0210 = 5A  A:00 X:00 Y:10 P:24 SP:FD PPU:  0, 21 CYC:{}", event.cycles));
+
+    // unofficial opcodes are marked in place of the leading space
+    let event = TraceEvent {
+        program_counter: 0xC6BD,
+        opcode: 0x04,
+        operands: [0xa9, 0x00],
+        operand_count: 1,
+        register_a: 0xaa,
+        register_x: 0x97,
+        register_y: 0x4e,
+        status: 0xef,
+        stack_pointer: 0xf5,
+        cycles: 14579
+    };
+    let mut bus = RAM::new();
+    bus.write(0x00a9, 0x00);
+    assert_eq!(event.nintendulator(&bus, 241, 130),
+        "C6BD  04 A9    *NOP $A9 = 00                    A:AA X:97 Y:4E P:EF SP:F5 PPU:241,130 CYC:14579");
+}
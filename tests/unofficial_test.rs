@@ -0,0 +1,179 @@
+use nesguin::emu6502::cpu::{CPU, CPUFlag, StepResult, StopCondition};
+
+/***
+ * Helper method for creating a CPU instance with program.
+ */
+fn load_test_program_to_cpu(program: Vec<u8>) -> CPU {
+    let mut cpu = CPU::new();
+    cpu.reset();
+    cpu.load_program(program);
+    cpu
+}
+
+#[test]
+fn test_op_lax_sax() {
+    // LDA #$f3, STA $10, *LAX $10, LDA #$0f, *SAX $11
+    let program = vec![0xa9, 0xf3, 0x85, 0x10, 0xa7, 0x10, 0xa9, 0x0f, 0x87, 0x11];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_x, 0xf3);
+    assert_eq!(cpu.memory.read_byte(0x11), 0x03);
+}
+
+#[test]
+fn test_op_dcp_isb() {
+    // LDA #$40, STA $10, *DCP $10, *ISB $10
+    let program = vec![0xa9, 0x40, 0x85, 0x10, 0xc7, 0x10, 0x38, 0xe7, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.step();
+    cpu.step();
+    cpu.step();
+    // DCP decremented memory to $3f, compare sets carry
+    assert_eq!(cpu.memory.read_byte(0x10), 0x3f);
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+    assert!(!cpu.get_cpu_flag(CPUFlag::Zero));
+    cpu.run();
+    // ISB incremented memory back to $40 and subtracted it
+    assert_eq!(cpu.memory.read_byte(0x10), 0x40);
+    assert_eq!(cpu.register_a, 0x00);
+    assert!(cpu.get_cpu_flag(CPUFlag::Zero));
+}
+
+#[test]
+fn test_op_slo_rla_sre_rra() {
+    // LDA #$81, STA $10, LDA #$01, *SLO $10
+    let program = vec![0xa9, 0x81, 0x85, 0x10, 0xa9, 0x01, 0x07, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.memory.read_byte(0x10), 0x02);
+    assert_eq!(cpu.register_a, 0x03);
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+
+    // SEC, LDA #$40, STA $10, LDA #$ff, *RLA $10
+    let program = vec![0x38, 0xa9, 0x40, 0x85, 0x10, 0xa9, 0xff, 0x27, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.memory.read_byte(0x10), 0x81);
+    assert_eq!(cpu.register_a, 0x81);
+
+    // LDA #$03, STA $10, LDA #$ff, *SRE $10
+    let program = vec![0xa9, 0x03, 0x85, 0x10, 0xa9, 0xff, 0x47, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.memory.read_byte(0x10), 0x01);
+    assert_eq!(cpu.register_a, 0xfe);
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+
+    // CLC, LDA #$03, STA $10, LDA #$10, *RRA $10 -> $01 + $10 + carry
+    let program = vec![0x18, 0xa9, 0x03, 0x85, 0x10, 0xa9, 0x10, 0x67, 0x10];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.memory.read_byte(0x10), 0x01);
+    assert_eq!(cpu.register_a, 0x12);
+}
+
+#[test]
+fn test_immediate_ops() {
+    // LDA #$ff, *ANC #$80
+    let program = vec![0xa9, 0xff, 0x0b, 0x80];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x80);
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+
+    // LDA #$ff, *ALR #$03
+    let program = vec![0xa9, 0xff, 0x4b, 0x03];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x01);
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+
+    // SEC, LDA #$ff, *ARR #$c0
+    let program = vec![0x38, 0xa9, 0xff, 0x6b, 0xc0];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0xe0);
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+    assert!(!cpu.get_cpu_flag(CPUFlag::Overflow));
+
+    // LDA #$0f, LDX #$fe, *AXS #$02
+    let program = vec![0xa9, 0x0f, 0xa2, 0xfe, 0xcb, 0x02];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_x, 0x0c);
+    assert!(cpu.get_cpu_flag(CPUFlag::Carry));
+
+    // SEC, LDA #$10, *SBC #$01
+    let program = vec![0x38, 0xa9, 0x10, 0xeb, 0x01];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x0f);
+}
+
+#[test]
+fn test_unofficial_nops() {
+    // *NOP #$ff, *NOP $10, *NOP $10f0,X, *NOP, INY
+    let program = vec![0x80, 0xff, 0x04, 0x10, 0x1c, 0xf0, 0x10, 0x1a, 0xc8];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.register_x = 0xff;
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 3 });
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 5 });
+    assert_eq!(cpu.step(), StepResult::Executed { cycles: 2 });
+    cpu.step();
+    assert_eq!(cpu.register_y, 0x01);
+}
+
+#[test]
+fn test_unstable_ops() {
+    // LDA #$ff, LDX #$0f, *XAA #$3c
+    let program = vec![0xa9, 0xff, 0xa2, 0x0f, 0x8b, 0x3c];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x0c);
+
+    // LDA #$00, *LXA #$5a
+    let program = vec![0xa9, 0x00, 0xab, 0x5a];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.register_a, 0x4a);
+    assert_eq!(cpu.register_x, 0x4a);
+
+    // LDY #$00, *LAS $2000,Y
+    let program = vec![0xa0, 0x00, 0xbb, 0x00, 0x20];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.memory.write_byte(0x2000, 0xf7);
+    cpu.run_until(StopCondition::Opcode(0x00));
+    assert_eq!(cpu.register_a, 0xf5);
+    assert_eq!(cpu.stack_pointer, 0xf5);
+
+    // LDX #$ff, LDY #$01, *SHY $20ff,X -> page crossed, high byte replaced by the stored value
+    let program = vec![0xa2, 0xff, 0xa0, 0x01, 0x9c, 0xff, 0x20];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.memory.read_byte(0x01fe), 0x01);
+
+    // LDA #$ff, LDX #$ff, *AHX $3000,Y with Y = 0
+    let program = vec![0xa9, 0xff, 0xa2, 0xff, 0x9f, 0x00, 0x30];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.run();
+    assert_eq!(cpu.memory.read_byte(0x3000), 0x31);
+}
+
+#[test]
+fn test_jam() {
+    // INX, *JAM
+    let program = vec![0xe8, 0x02, 0xe8];
+    let mut cpu = load_test_program_to_cpu(program);
+    cpu.step();
+    assert_eq!(cpu.step(), StepResult::Jammed);
+    assert!(cpu.jammed);
+    // stays halted, even for interrupts
+    cpu.set_nmi_line(true);
+    assert_eq!(cpu.step(), StepResult::Jammed);
+    assert_eq!(cpu.program_counter, 0x8001);
+    assert_eq!(cpu.register_x, 0x01);
+    // reset recovers the CPU
+    cpu.reset();
+    assert!(!cpu.jammed);
+}